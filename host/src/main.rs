@@ -0,0 +1,60 @@
+mod record;
+
+use record::{write_csv_header, write_csv_row, Decoder};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
+
+/**
+ * Receives watch telemetry from the everdrive usb link
+ * and writes it as csv.
+ * The input can be the usb serial device (set it to raw mode first,
+ * e.g. stty -F /dev/ttyUSB0 raw) or a file that was captured earlier.
+ *
+ * usage: ultrarstelemetry <input|-> [output.csv]
+ */
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <input|-> [output.csv]", args[0]);
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&args[1], args.get(2)) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(input: &str, output: Option<&String>) -> io::Result<()> {
+    let mut input: Box<dyn Read> = if input == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(input)?)
+    };
+
+    let mut output: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    write_csv_header(&mut output)?;
+
+    let mut decoder = Decoder::new();
+    let mut buffer = [0u8; 512];
+    loop {
+        let len = input.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+
+        decoder.push(&buffer[..len]);
+        while let Some(record) = decoder.next_record() {
+            write_csv_row(&mut output, &record)?;
+        }
+        output.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,369 @@
+/// must match lib/src/movie.rs
+pub const MOVIE_MAGIC: [u8; 4] = *b"UMOV";
+pub const MOVIE_VERSION: u16 = 1;
+pub const HEADER_LEN: usize = 40;
+pub const MAX_CONTROLLERS: usize = 4;
+
+pub const M64_MAGIC: [u8; 4] = *b"M64\x1A";
+pub const M64_VERSION: u32 = 3;
+pub const M64_HEADER_LEN: usize = 0x400;
+
+/// m64 start types
+const M64_START_SNAPSHOT: u16 = 1;
+const M64_START_POWER_ON: u16 = 2;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct RomId {
+    pub crc1: u32,
+    pub crc2: u32,
+    /// cartridge id, country code and version
+    pub code: [u8; 4],
+}
+
+impl RomId {
+    /**
+     * Reads the id from a rom image
+     * z64 (big endian), v64 (byte swapped) and n64 (little endian) work
+     */
+    pub fn from_rom(rom: &[u8]) -> Result<Self, String> {
+        if rom.len() < 0x40 {
+            return Err("rom is too small".into());
+        }
+
+        let header: Vec<u8> = match rom[0..4] {
+            [0x80, 0x37, 0x12, 0x40] => rom[..0x40].to_vec(),
+            [0x37, 0x80, 0x40, 0x12] => rom[..0x40]
+                .chunks_exact(2)
+                .flat_map(|c| [c[1], c[0]])
+                .collect(),
+            [0x40, 0x12, 0x37, 0x80] => rom[..0x40]
+                .chunks_exact(4)
+                .flat_map(|c| [c[3], c[2], c[1], c[0]])
+                .collect(),
+            _ => return Err("not a n64 rom".into()),
+        };
+
+        Ok(Self {
+            crc1: be32(&header[0x10..]),
+            crc2: be32(&header[0x14..]),
+            code: [header[0x3C], header[0x3D], header[0x3E], header[0x3F]],
+        })
+    }
+
+    /// by the country code
+    pub fn is_pal(&self) -> bool {
+        matches!(
+            self.code[2],
+            b'D' | b'F' | b'I' | b'P' | b'S' | b'U' | b'X' | b'Y'
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Movie {
+    pub controllers: u8,
+    pub frames: u32,
+    pub rerecords: u32,
+    pub rom: RomId,
+    /// 0 starts at power on
+    pub savestate: u32,
+    /// one sample per controller and frame, as in pif ram
+    pub samples: Vec<u32>,
+}
+
+fn be32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn le32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn le16(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+/// same as the checksum on the console
+pub fn checksum(samples: &[u32]) -> u32 {
+    samples
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .fold(0u32, |sum, b| sum.rotate_left(1).wrapping_add(b as u32))
+}
+
+fn check_controllers(controllers: usize) -> Result<(), String> {
+    if controllers == 0 || controllers > MAX_CONTROLLERS {
+        Err(format!("unsupported controller count {}", controllers))
+    } else {
+        Ok(())
+    }
+}
+
+/// reads frames * controllers samples from the start of data
+fn read_samples(
+    data: &[u8],
+    frames: u32,
+    controllers: usize,
+    read: fn(&[u8]) -> u32,
+) -> Result<Vec<u32>, String> {
+    let count = (frames as usize)
+        .checked_mul(controllers)
+        .ok_or("movie is too long")?;
+    if data.len() / 4 < count {
+        return Err("movie is truncated".into());
+    }
+    Ok(data.chunks_exact(4).take(count).map(read).collect())
+}
+
+impl Movie {
+    /// reads a movie that was saved by the console
+    pub fn from_umov(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_LEN || data[0..4] != MOVIE_MAGIC {
+            return Err("not a movie".into());
+        }
+        let version = u16::from_be_bytes([data[4], data[5]]);
+        if version != MOVIE_VERSION {
+            return Err(format!("unsupported movie version {}", version));
+        }
+
+        let controllers = data[6];
+        check_controllers(controllers as usize)?;
+        let frames = be32(&data[8..]);
+        let samples = read_samples(&data[HEADER_LEN..], frames, controllers as usize, be32)?;
+
+        if checksum(&samples) != be32(&data[32..]) {
+            return Err("checksum does not match".into());
+        }
+
+        Ok(Self {
+            controllers,
+            frames,
+            rerecords: be32(&data[12..]),
+            rom: RomId {
+                crc1: be32(&data[16..]),
+                crc2: be32(&data[20..]),
+                code: [data[24], data[25], data[26], data[27]],
+            },
+            savestate: be32(&data[28..]),
+            samples,
+        })
+    }
+
+    pub fn to_umov(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.samples.len() * 4);
+        data.extend_from_slice(&MOVIE_MAGIC);
+        data.extend_from_slice(&MOVIE_VERSION.to_be_bytes());
+        data.push(self.controllers);
+        data.push(0);
+
+        let words = [
+            self.frames,
+            self.rerecords,
+            self.rom.crc1,
+            self.rom.crc2,
+            u32::from_be_bytes(self.rom.code),
+            self.savestate,
+            checksum(&self.samples),
+            0,
+        ];
+        for word in words {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        for sample in &self.samples {
+            data.extend_from_slice(&sample.to_be_bytes());
+        }
+        data
+    }
+
+    /**
+     * Reads a mupen64 movie
+     * m64 files only store the first crc of the rom,
+     * so the id of the rom the movie is played on has to be passed in.
+     * The controller data in m64 files has the same byte order
+     * as the controller status in pif ram.
+     */
+    pub fn from_m64(data: &[u8], rom: RomId) -> Result<Self, String> {
+        if data.len() < M64_HEADER_LEN || data[0..4] != M64_MAGIC {
+            return Err("not a m64 movie".into());
+        }
+        let version = le32(&data[4..]);
+        if version != M64_VERSION {
+            return Err(format!("unsupported m64 version {}", version));
+        }
+
+        // controllers have to be present from port 1 on
+        let flags = le32(&data[0x20..]) & 0xF;
+        let controllers = (flags + 1).trailing_zeros() as usize;
+        if flags >> controllers != 0 {
+            return Err("only movies with consecutive controllers from port 1 work".into());
+        }
+        check_controllers(controllers)?;
+
+        let crc1 = le32(&data[0xE4..]);
+        if crc1 != rom.crc1 {
+            return Err(format!(
+                "movie is for rom crc {:08X}, not {:08X}",
+                crc1, rom.crc1
+            ));
+        }
+
+        let frames = le32(&data[0x18..]);
+        let samples = read_samples(&data[M64_HEADER_LEN..], frames, controllers, be32)?;
+
+        Ok(Self {
+            controllers: controllers as u8,
+            frames,
+            rerecords: le32(&data[0x10..]),
+            rom,
+            savestate: match le16(&data[0x1C..]) {
+                M64_START_POWER_ON => 0,
+                _ => 1,
+            },
+            samples,
+        })
+    }
+
+    pub fn to_m64(&self) -> Vec<u8> {
+        let mut data = vec![0u8; M64_HEADER_LEN];
+        data[0..4].copy_from_slice(&M64_MAGIC);
+        data[4..8].copy_from_slice(&M64_VERSION.to_le_bytes());
+        // the vi count is unknown, one vi per frame is the closest guess
+        data[0x0C..0x10].copy_from_slice(&self.frames.to_le_bytes());
+        data[0x10..0x14].copy_from_slice(&self.rerecords.to_le_bytes());
+        data[0x14] = if self.rom.is_pal() { 50 } else { 60 };
+        data[0x15] = self.controllers;
+        data[0x18..0x1C].copy_from_slice(&self.frames.to_le_bytes());
+
+        let start = match self.savestate {
+            0 => M64_START_POWER_ON,
+            _ => M64_START_SNAPSHOT,
+        };
+        data[0x1C..0x1E].copy_from_slice(&start.to_le_bytes());
+
+        let flags = (1u32 << self.controllers) - 1;
+        data[0x20..0x24].copy_from_slice(&flags.to_le_bytes());
+        data[0xE4..0xE8].copy_from_slice(&self.rom.crc1.to_le_bytes());
+        data[0xE8] = self.rom.code[2];
+
+        for sample in &self.samples {
+            data.extend_from_slice(&sample.to_be_bytes());
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(controllers: u8, savestate: u32) -> Movie {
+        let frames = 3;
+        Movie {
+            controllers,
+            frames,
+            rerecords: 7,
+            rom: RomId {
+                crc1: 0x635A2BFF,
+                crc2: 0x8B022326,
+                code: *b"SME\0",
+            },
+            savestate,
+            samples: (0..frames * controllers as u32)
+                .map(|i| 0x80004F00 | i)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn umov_round_trip() {
+        for controllers in 1..=4 {
+            let movie = movie(controllers, 5);
+            let data = movie.to_umov();
+            assert_eq!(data.len(), HEADER_LEN + movie.samples.len() * 4);
+            assert_eq!(Movie::from_umov(&data), Ok(movie));
+        }
+    }
+
+    #[test]
+    fn umov_errors() {
+        let mut data = movie(2, 0).to_umov();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(Movie::from_umov(&data).is_err());
+
+        let data = movie(2, 0).to_umov();
+        assert!(Movie::from_umov(&data[..data.len() - 4]).is_err());
+
+        let mut data = movie(2, 0).to_umov();
+        data[6] = 5;
+        assert!(Movie::from_umov(&data).is_err());
+
+        let mut data = movie(2, 0).to_umov();
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Movie::from_umov(&data).is_err());
+    }
+
+    #[test]
+    fn m64_round_trip() {
+        for controllers in 1..=4 {
+            // m64 only knows power on and snapshot starts
+            for savestate in [0, 1] {
+                let movie = movie(controllers, savestate);
+                let data = movie.to_m64();
+                assert_eq!(data.len(), M64_HEADER_LEN + movie.samples.len() * 4);
+                assert_eq!(Movie::from_m64(&data, movie.rom), Ok(movie));
+            }
+        }
+    }
+
+    #[test]
+    fn m64_header() {
+        let data = movie(2, 0).to_m64();
+        assert_eq!(data[0x14], 60);
+        assert_eq!(data[0x15], 2);
+        assert_eq!(le32(&data[0x20..]), 0b11);
+        assert_eq!(le16(&data[0x1C..]), M64_START_POWER_ON);
+        assert_eq!(data[0xE8], b'E');
+    }
+
+    #[test]
+    fn m64_errors() {
+        let movie = movie(1, 0);
+        let other = RomId {
+            crc1: 1,
+            ..movie.rom
+        };
+        assert!(Movie::from_m64(&movie.to_m64(), other).is_err());
+
+        // port 2 without port 1
+        let mut data = movie.to_m64();
+        data[0x20..0x24].copy_from_slice(&2u32.to_le_bytes());
+        assert!(Movie::from_m64(&data, movie.rom).is_err());
+
+        let data = movie.to_m64();
+        assert!(Movie::from_m64(&data[..data.len() - 1], movie.rom).is_err());
+    }
+
+    #[test]
+    fn rom_byte_orders() {
+        let mut z64 = vec![0u8; 0x40];
+        z64[0..4].copy_from_slice(&[0x80, 0x37, 0x12, 0x40]);
+        z64[0x10..0x14].copy_from_slice(&0x635A2BFFu32.to_be_bytes());
+        z64[0x14..0x18].copy_from_slice(&0x8B022326u32.to_be_bytes());
+        z64[0x3C..0x40].copy_from_slice(b"SMP\0");
+        let v64: Vec<u8> = z64.chunks_exact(2).flat_map(|c| [c[1], c[0]]).collect();
+        let n64: Vec<u8> = z64
+            .chunks_exact(4)
+            .flat_map(|c| [c[3], c[2], c[1], c[0]])
+            .collect();
+
+        let id = RomId::from_rom(&z64).unwrap();
+        assert_eq!(id.crc1, 0x635A2BFF);
+        assert_eq!(id.crc2, 0x8B022326);
+        assert_eq!(&id.code, b"SMP\0");
+        assert!(id.is_pal());
+        assert_eq!(RomId::from_rom(&v64), Ok(id));
+        assert_eq!(RomId::from_rom(&n64), Ok(id));
+        assert!(RomId::from_rom(&z64[..0x20]).is_err());
+    }
+}
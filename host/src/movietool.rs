@@ -0,0 +1,52 @@
+mod movie;
+
+use movie::{Movie, RomId};
+use std::env;
+use std::fs;
+use std::process;
+
+/**
+ * Converts between movies saved by the console and mupen64 m64 movies
+ * Console movies can be captured from the usb device (e.g. cat /dev/ttyUSB0 > movie.umov)
+ * and sent back by writing them to the device while the console waits for them.
+ *
+ * usage:
+ * ultrarsmovie export <movie.umov> <out.m64>
+ * ultrarsmovie import <movie.m64> <rom.z64> <out.umov>
+ */
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        Some("export") if args.len() == 4 => export(&args[2], &args[3]),
+        Some("import") if args.len() == 5 => import(&args[2], &args[3], &args[4]),
+        _ => {
+            eprintln!("usage: {} export <movie.umov> <out.m64>", args[0]);
+            eprintln!("       {} import <movie.m64> <rom.z64> <out.umov>", args[0]);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn write(path: &str, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn export(input: &str, output: &str) -> Result<(), String> {
+    let movie = Movie::from_umov(&read(input)?)?;
+    write(output, &movie.to_m64())
+}
+
+fn import(input: &str, rom: &str, output: &str) -> Result<(), String> {
+    let rom = RomId::from_rom(&read(rom)?)?;
+    let movie = Movie::from_m64(&read(input)?, rom)?;
+    write(output, &movie.to_umov())
+}
@@ -0,0 +1,271 @@
+use std::io::{self, Write};
+
+/// must match lib/src/telemetry.rs
+pub const RECORD_MAGIC: [u8; 2] = *b"TL";
+pub const HEADER_LEN: usize = 8;
+pub const ERROR_FLAG: u8 = 0x80;
+pub const MAX_WATCHES: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Value {
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    /// the watch could not be read that frame
+    Error,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    pub frame: u32,
+    pub values: [Option<Value>; MAX_WATCHES],
+}
+
+/// size in bytes and signedness of a type id from WatchType::id
+fn type_info(id: u8) -> Option<(usize, bool)> {
+    match id {
+        0 => Some((4, false)),
+        1 => Some((1, true)),
+        2 => Some((2, true)),
+        3 => Some((4, true)),
+        4 => Some((8, true)),
+        5 => Some((1, false)),
+        6 => Some((2, false)),
+        7 => Some((4, false)),
+        8 => Some((8, false)),
+        _ => None,
+    }
+}
+
+fn decode_value(id: u8, bytes: &[u8]) -> Value {
+    let raw = bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+    let shift = 64 - bytes.len() * 8;
+    match (id, type_info(id)) {
+        (0, _) => Value::Float(f32::from_bits(raw as u32)),
+        (_, Some((_, true))) => Value::Int(((raw << shift) as i64) >> shift),
+        _ => Value::UInt(raw),
+    }
+}
+
+/**
+ * Decodes the record at the start of data
+ * returns None if data does not start with a valid record
+ */
+fn decode(data: &[u8]) -> Option<Record> {
+    if data.len() < HEADER_LEN || data[0..2] != RECORD_MAGIC {
+        return None;
+    }
+
+    let count = data[2] as usize;
+    let len = data[3] as usize;
+    if len < HEADER_LEN || !len.is_multiple_of(4) || len > data.len() {
+        return None;
+    }
+
+    let mut record = Record {
+        frame: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        values: [None; MAX_WATCHES],
+    };
+
+    let mut pos = HEADER_LEN;
+    for _ in 0..count {
+        if pos + 2 > len {
+            return None;
+        }
+        let index = (data[pos] & !ERROR_FLAG) as usize;
+        let id = data[pos + 1];
+        let (size, _) = type_info(id)?;
+        if index >= MAX_WATCHES {
+            return None;
+        }
+        pos += 2;
+
+        if data[pos - 2] & ERROR_FLAG != 0 {
+            record.values[index] = Some(Value::Error);
+        } else {
+            if pos + size > len {
+                return None;
+            }
+            record.values[index] = Some(decode_value(id, &data[pos..pos + size]));
+            pos += size;
+        }
+    }
+
+    Some(record)
+}
+
+/**
+ * Collects bytes from the usb link and splits them into records
+ * anything that is not a record (e.g. dumps or trigger events)
+ * is skipped until the next record starts
+ */
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// returns the next complete record
+    pub fn next_record(&mut self) -> Option<Record> {
+        loop {
+            let start = self.buffer.windows(2).position(|w| w == RECORD_MAGIC)?;
+            self.buffer.drain(..start);
+
+            if self.buffer.len() < HEADER_LEN {
+                return None;
+            }
+
+            let len = self.buffer[3] as usize;
+            if len >= HEADER_LEN && len.is_multiple_of(4) && self.buffer.len() < len {
+                // wait for the rest of the record
+                return None;
+            }
+
+            match decode(&self.buffer) {
+                Some(record) => {
+                    self.buffer.drain(..len);
+                    return Some(record);
+                }
+                None => {
+                    // not a record after all, keep searching after the magic
+                    self.buffer.drain(..1);
+                }
+            }
+        }
+    }
+}
+
+pub fn write_csv_header(out: &mut dyn Write) -> io::Result<()> {
+    write!(out, "frame")?;
+    for i in 0..MAX_WATCHES {
+        write!(out, ",w{}", i)?;
+    }
+    writeln!(out)
+}
+
+/// watches that were not active that frame are left empty
+pub fn write_csv_row(out: &mut dyn Write, record: &Record) -> io::Result<()> {
+    write!(out, "{}", record.frame)?;
+    for value in &record.values {
+        match value {
+            Some(Value::Int(v)) => write!(out, ",{}", v)?,
+            Some(Value::UInt(v)) => write!(out, ",{}", v)?,
+            Some(Value::Float(v)) => write!(out, ",{}", v)?,
+            Some(Value::Error) => write!(out, ",err")?,
+            None => write!(out, ",")?,
+        }
+    }
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// frame 0x102, watch 0 u16 0xBEEF, watch 2 s8 -2, watch 3 failed
+    const RECORD: [u8; 20] = [
+        b'T',
+        b'L',
+        3,
+        20,
+        0x00,
+        0x00,
+        0x01,
+        0x02, // header
+        0,
+        6,
+        0xBE,
+        0xEF, // u16
+        2,
+        1,
+        0xFE, // s8
+        3 | ERROR_FLAG,
+        7, // u32 error
+        0,
+        0,
+        0, // padding
+    ];
+
+    fn record() -> Record {
+        let mut values = [None; MAX_WATCHES];
+        values[0] = Some(Value::UInt(0xBEEF));
+        values[2] = Some(Value::Int(-2));
+        values[3] = Some(Value::Error);
+        Record {
+            frame: 0x102,
+            values,
+        }
+    }
+
+    #[test]
+    fn decodes_values() {
+        assert_eq!(decode(&RECORD), Some(record()));
+    }
+
+    #[test]
+    fn decodes_floats() {
+        let mut data = vec![b'T', b'L', 1, 16, 0, 0, 0, 0, 1, 0];
+        data.extend_from_slice(&1.5f32.to_bits().to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        let record = decode(&data).unwrap();
+        assert_eq!(record.values[1], Some(Value::Float(1.5)));
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        // bad length
+        let mut data = RECORD;
+        data[3] = 18;
+        assert_eq!(decode(&data), None);
+        // unknown type id
+        let mut data = RECORD;
+        data[9] = 42;
+        assert_eq!(decode(&data), None);
+        // index out of range
+        let mut data = RECORD;
+        data[8] = MAX_WATCHES as u8;
+        assert_eq!(decode(&data), None);
+        // values longer than the record
+        let mut data = RECORD;
+        data[2] = 5;
+        assert_eq!(decode(&data), None);
+        assert_eq!(decode(&RECORD[..12]), None);
+    }
+
+    #[test]
+    fn decoder_skips_garbage_and_waits_for_data() {
+        let mut decoder = Decoder::new();
+        decoder.push(b"dump T");
+        decoder.push(&RECORD[..10]);
+        assert_eq!(decoder.next_record(), None);
+
+        decoder.push(&RECORD[10..]);
+        decoder.push(&RECORD);
+        assert_eq!(decoder.next_record(), Some(record()));
+        assert_eq!(decoder.next_record(), Some(record()));
+        assert_eq!(decoder.next_record(), None);
+    }
+
+    #[test]
+    fn decoder_resyncs_after_a_false_magic() {
+        let mut decoder = Decoder::new();
+        decoder.push(b"TL\x00\x05abcd");
+        decoder.push(&RECORD);
+        assert_eq!(decoder.next_record(), Some(record()));
+    }
+
+    #[test]
+    fn csv_rows() {
+        let mut out = Vec::new();
+        write_csv_row(&mut out, &record()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "258,48879,,-2,err,,,,\n");
+    }
+}
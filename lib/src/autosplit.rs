@@ -0,0 +1,167 @@
+use crate::memmap::{MemoryAccess, MemoryMap};
+use crate::splits::Splits;
+use crate::trigger::Condition;
+use crate::watch::WatchType;
+
+pub const MAX_RULES: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SplitEvent {
+    Start,
+    Split,
+    Reset,
+}
+
+/**
+ * A memory condition that starts, splits or resets the timer
+ * e.g. split when the level id at 0x8033B24A changes to 5:
+ * Rule::new(SplitEvent::Split, WatchType::UInt16(0x8033B24A as *const u16), Condition::Equals(5))
+ */
+#[derive(Copy, Clone)]
+pub struct Rule {
+    pub event: SplitEvent,
+    pub watch_type: WatchType,
+    pub condition: Condition,
+    /// only split when this segment is the current one
+    pub segment: Option<usize>,
+    previous: Option<u64>,
+}
+
+impl Rule {
+    pub fn new(event: SplitEvent, watch_type: WatchType, condition: Condition) -> Self {
+        Self {
+            event,
+            watch_type,
+            condition,
+            segment: None,
+            previous: None,
+        }
+    }
+
+    pub fn for_segment(mut self, segment: usize) -> Self {
+        self.segment = Some(segment);
+        self
+    }
+
+    /**
+     * Reads the value and returns true if the rule fires.
+     * Level conditions (equals, bit set) only fire
+     * when they become true, not while they stay true,
+     * so a value that is already set on boot does not fire.
+     */
+    pub fn update<M: MemoryAccess>(&mut self, memory: &M) -> bool {
+        let previous = self.previous;
+        let current = self.watch_type.read(memory).ok();
+        self.previous = current;
+
+        let current = match current {
+            Some(current) => current,
+            None => return false,
+        };
+
+        if !self.condition.is_met(previous, current, &self.watch_type) {
+            return false;
+        }
+
+        self.condition.is_edge()
+            || previous
+                .is_some_and(|previous| !self.condition.is_met(None, previous, &self.watch_type))
+    }
+}
+
+/// the rules that fired in a frame
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct Events {
+    pub start: bool,
+    pub split: bool,
+    pub reset: bool,
+}
+
+/**
+ * Evaluates memory rules every frame
+ * and drives a split timer with them
+ */
+pub struct AutoSplitter {
+    pub enabled: bool,
+    rules: [Option<Rule>; MAX_RULES],
+    pub memory: MemoryMap,
+}
+
+impl AutoSplitter {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            rules: [None; MAX_RULES],
+            memory: MemoryMap::detect(),
+        }
+    }
+
+    /// returns the index of the rule or None if all slots are used
+    pub fn add(&mut self, rule: Rule) -> Option<usize> {
+        let index = self.rules.iter().position(|r| r.is_none())?;
+        self.rules[index] = Some(rule);
+        Some(index)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Rule> {
+        self.rules.get_mut(index)?.take()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Rule> {
+        self.rules.get(index)?.as_ref()
+    }
+
+    pub fn clear(&mut self) {
+        self.rules = [None; MAX_RULES];
+    }
+
+    /**
+     * Updates every rule, even the ones that do not apply right now,
+     * so their previous values are always from the last frame
+     */
+    pub fn poll<M: MemoryAccess>(&mut self, memory: &M, segment: usize) -> Events {
+        let mut events = Events::default();
+
+        for rule in self.rules.iter_mut().flatten() {
+            if !rule.update(memory) {
+                continue;
+            }
+
+            match rule.event {
+                SplitEvent::Start => events.start = true,
+                SplitEvent::Reset => events.reset = true,
+                SplitEvent::Split => {
+                    events.split |= rule.segment.unwrap_or(segment) == segment;
+                }
+            }
+        }
+        events
+    }
+
+    /// call once a frame before the splits are updated
+    pub fn update<T: Copy + Clone>(&mut self, splits: &mut Splits<T>) {
+        if !self.enabled {
+            return;
+        }
+
+        let memory = self.memory;
+        let events = self.poll(&memory, splits.current());
+
+        // a reset and start in the same frame starts a new run
+        if events.reset {
+            splits.reset();
+        }
+
+        if events.start && !splits.timer.running() && splits.current() == 0 {
+            splits.start();
+        } else if events.split && splits.timer.running() {
+            splits.split();
+        }
+    }
+}
+
+impl Default for AutoSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,122 @@
+use crate::color::Color;
+use crate::memmap::MemoryMap;
+
+/// the largest page that can be tracked
+pub const TRACK_LEN: usize = 64;
+
+/**
+ * Keeps a copy of a memory page
+ * and counts down an age for every byte
+ * that changed since the last update.
+ * A byte with an age of 0 has not changed
+ * during the last fade_frames updates.
+ */
+pub struct ChangeTracker {
+    base: usize,
+    len: usize,
+    previous: [u8; TRACK_LEN],
+    valid: [bool; TRACK_LEN],
+    ages: [u8; TRACK_LEN],
+    pub fade_frames: u8,
+}
+
+impl ChangeTracker {
+    pub fn new(fade_frames: u8) -> Self {
+        Self {
+            base: 0,
+            len: 0,
+            previous: [0; TRACK_LEN],
+            valid: [false; TRACK_LEN],
+            ages: [0; TRACK_LEN],
+            fade_frames,
+        }
+    }
+
+    /**
+     * Takes a fresh snapshot without marking
+     * anything as changed
+     */
+    pub fn reset(&mut self, memory: &MemoryMap, base: usize, len: usize) {
+        self.base = base;
+        self.len = usize::min(len, TRACK_LEN);
+        self.ages.fill(0);
+
+        for i in 0..self.len {
+            match memory.read_u8(base + i) {
+                Ok(value) => {
+                    self.previous[i] = value;
+                    self.valid[i] = true;
+                }
+                Err(_) => self.valid[i] = false,
+            }
+        }
+    }
+
+    /**
+     * Call once a frame
+     * if the page moved the snapshot is retaken
+     */
+    pub fn update(&mut self, memory: &MemoryMap, base: usize, len: usize) {
+        if base != self.base || usize::min(len, TRACK_LEN) != self.len {
+            self.reset(memory, base, len);
+            return;
+        }
+
+        for i in 0..self.len {
+            if self.ages[i] > 0 {
+                self.ages[i] -= 1;
+            }
+
+            if !self.valid[i] {
+                continue;
+            }
+
+            if let Ok(value) = memory.read_u8(base + i) {
+                if value != self.previous[i] {
+                    self.previous[i] = value;
+                    self.ages[i] = self.fade_frames;
+                }
+            }
+        }
+    }
+
+    pub fn age(&self, offset: usize) -> u8 {
+        if offset < self.len {
+            self.ages[offset]
+        } else {
+            0
+        }
+    }
+
+    pub fn changed(&self, offset: usize) -> bool {
+        self.age(offset) > 0
+    }
+
+    /**
+     * Color for a changed byte
+     * fades out as the age runs down
+     */
+    pub fn highlight(&self, offset: usize) -> Option<Color> {
+        let age = self.age(offset) as u32;
+        if age == 0 || self.fade_frames == 0 {
+            return None;
+        }
+
+        let intensity = 0x40 + (0xBF * age / self.fade_frames as u32) as u8;
+        Some(Color::new(0x00, intensity, 0x00, intensity))
+    }
+
+    /// value of the byte at offset as of the last update
+    pub fn value(&self, offset: usize) -> Option<u8> {
+        if offset < self.len && self.valid[offset] {
+            Some(self.previous[offset])
+        } else {
+            None
+        }
+    }
+
+    /// offsets of all bytes that changed during the last fade_frames updates
+    pub fn changed_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |i| self.ages[*i] > 0)
+    }
+}
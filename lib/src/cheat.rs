@@ -0,0 +1,689 @@
+use crate::color::Color;
+use crate::memmap::{MemoryAccess, MemoryError, KSEG0, KSEG1};
+use crate::memory::SharedPtrCell;
+use crate::menu::EntryTypes;
+use crate::render::{Drawable, RenderContext, Widget};
+
+/**
+ * GameShark / Action Replay code engine
+ * A code is a 32 bit address word followed by a 16 bit value:
+ *   80XXXXXX 00YY  8 bit write
+ *   81XXXXXX YYYY  16 bit write
+ *   A0XXXXXX 00YY  8 bit uncached write
+ *   A1XXXXXX YYYY  16 bit uncached write
+ *   D0XXXXXX 00YY  execute next code if 8 bit value equals
+ *   D1XXXXXX YYYY  execute next code if 16 bit value equals
+ *   D2XXXXXX 00YY  execute next code if 8 bit value differs
+ *   D3XXXXXX YYYY  execute next code if 16 bit value differs
+ *   5000NNSS IIII  repeat the next write NN times, stepping the address by SS
+ *                  and the value by IIII
+ *   F0XXXXXX 00YY  8 bit boot write
+ *   F1XXXXXX YYYY  16 bit boot write
+ */
+
+pub const MAX_CODES: usize = 32;
+pub const MAX_GROUPS: usize = 16;
+pub const MAX_NAME_LEN: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CheatError {
+    /// not 12 hex digits
+    InvalidFormat,
+    UnknownType,
+    InvalidAddress,
+    /// 8 bit codes with a value above 0xFF
+    InvalidValue,
+    /// a conditional or repeater without a code to act on
+    MissingTarget,
+    /// repeaters can only be followed by writes
+    InvalidRepeat,
+    Full,
+    Memory(MemoryError),
+}
+
+impl From<MemoryError> for CheatError {
+    fn from(err: MemoryError) -> Self {
+        CheatError::Memory(err)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CodeType {
+    Write8,
+    Write16,
+    IfEqual8,
+    IfEqual16,
+    IfNotEqual8,
+    IfNotEqual16,
+    Repeat,
+    Boot8,
+    Boot16,
+}
+
+impl CodeType {
+    pub fn is_write(&self) -> bool {
+        matches!(self, CodeType::Write8 | CodeType::Write16)
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        matches!(
+            self,
+            CodeType::IfEqual8
+                | CodeType::IfEqual16
+                | CodeType::IfNotEqual8
+                | CodeType::IfNotEqual16
+        )
+    }
+
+    pub fn is_16bit(&self) -> bool {
+        matches!(
+            self,
+            CodeType::Write16 | CodeType::IfEqual16 | CodeType::IfNotEqual16 | CodeType::Boot16
+        )
+    }
+}
+
+/**
+ * A single decoded code
+ * For repeaters addr holds the raw address word
+ */
+#[derive(Copy, Clone)]
+pub struct Code {
+    pub kind: CodeType,
+    pub addr: usize,
+    pub value: u16,
+}
+
+impl Code {
+    pub fn new(word: u32, value: u16) -> Result<Self, CheatError> {
+        let offset = (word & 0x00FFFFFF) as usize;
+
+        let (kind, addr) = match word >> 24 {
+            0x80 => (CodeType::Write8, KSEG0 | offset),
+            0x81 => (CodeType::Write16, KSEG0 | offset),
+            0xA0 => (CodeType::Write8, KSEG1 | offset),
+            0xA1 => (CodeType::Write16, KSEG1 | offset),
+            0xD0 => (CodeType::IfEqual8, KSEG0 | offset),
+            0xD1 => (CodeType::IfEqual16, KSEG0 | offset),
+            0xD2 => (CodeType::IfNotEqual8, KSEG0 | offset),
+            0xD3 => (CodeType::IfNotEqual16, KSEG0 | offset),
+            0x50 => (CodeType::Repeat, word as usize),
+            0xF0 => (CodeType::Boot8, KSEG0 | offset),
+            0xF1 => (CodeType::Boot16, KSEG0 | offset),
+            _ => return Err(CheatError::UnknownType),
+        };
+
+        let code = Self { kind, addr, value };
+        code.validate()?;
+        Ok(code)
+    }
+
+    /**
+     * Parses a code in the usual "XXXXXXXX YYYY" notation
+     * spaces, ':' and '-' are ignored
+     */
+    pub fn parse(text: &[u8]) -> Result<Self, CheatError> {
+        let mut raw: u64 = 0;
+        let mut digits = 0;
+
+        for c in text {
+            let digit = match c {
+                b'0'..=b'9' => c - b'0',
+                b'A'..=b'F' => c - b'A' + 10,
+                b'a'..=b'f' => c - b'a' + 10,
+                b' ' | b':' | b'-' | b'\t' | b'\r' => continue,
+                b'\0' => break,
+                _ => return Err(CheatError::InvalidFormat),
+            };
+            raw = (raw << 4) | digit as u64;
+            digits += 1;
+        }
+
+        if digits != 12 {
+            return Err(CheatError::InvalidFormat);
+        }
+
+        Self::new((raw >> 16) as u32, raw as u16)
+    }
+
+    fn validate(&self) -> Result<(), CheatError> {
+        if self.kind == CodeType::Repeat {
+            // the repeater has no address, but its second byte must be 0
+            if self.addr & 0x00FF0000 != 0 {
+                return Err(CheatError::UnknownType);
+            }
+            return Ok(());
+        }
+
+        if self.kind.is_16bit() {
+            if self.addr % 2 != 0 {
+                return Err(CheatError::InvalidAddress);
+            }
+        } else if self.value > 0xFF {
+            return Err(CheatError::InvalidValue);
+        }
+
+        Ok(())
+    }
+
+    /// count, address step and value step of a repeater
+    pub fn repeat(&self) -> (usize, usize, u16) {
+        ((self.addr >> 8) & 0xFF, self.addr & 0xFF, self.value)
+    }
+
+    fn write<M: MemoryAccess>(
+        &self,
+        memory: &M,
+        addr: usize,
+        value: u16,
+    ) -> Result<(), CheatError> {
+        if self.kind.is_16bit() {
+            memory.write_u16(addr, value)?;
+        } else {
+            memory.write_u8(addr, value as u8)?;
+        }
+        Ok(())
+    }
+
+    fn condition<M: MemoryAccess>(&self, memory: &M) -> Result<bool, CheatError> {
+        let current = if self.kind.is_16bit() {
+            memory.read_u16(self.addr)?
+        } else {
+            memory.read_u8(self.addr)? as u16
+        };
+
+        Ok(match self.kind {
+            CodeType::IfEqual8 | CodeType::IfEqual16 => current == self.value,
+            _ => current != self.value,
+        })
+    }
+}
+
+/**
+ * A named list of codes that is
+ * toggled as a whole
+ */
+#[derive(Copy, Clone)]
+pub struct CheatGroup {
+    pub name: [u8; MAX_NAME_LEN],
+    codes: [Option<Code>; MAX_CODES],
+    len: usize,
+    pub active: bool,
+    /// boot codes only run once per activation
+    booted: bool,
+}
+
+impl CheatGroup {
+    pub fn new(name: &str) -> Self {
+        let mut name_buffer = [0; MAX_NAME_LEN];
+        // keep the last byte as terminator
+        for (i, c) in name.bytes().take(MAX_NAME_LEN - 1).enumerate() {
+            name_buffer[i] = c;
+        }
+
+        Self {
+            name: name_buffer,
+            codes: [None; MAX_CODES],
+            len: 0,
+            active: false,
+            booted: false,
+        }
+    }
+
+    /**
+     * Creates a group from a list of codes
+     * separated by new lines or ';'
+     */
+    pub fn parse(name: &str, text: &str) -> Result<Self, CheatError> {
+        let mut group = Self::new(name);
+        for line in text.split(['\n', ';']) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            group.push(Code::parse(line.as_bytes())?)?;
+        }
+        group.validate()?;
+        Ok(group)
+    }
+
+    pub fn push(&mut self, code: Code) -> Result<(), CheatError> {
+        if self.len >= MAX_CODES {
+            return Err(CheatError::Full);
+        }
+        self.codes[self.len] = Some(code);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn codes(&self) -> impl Iterator<Item = &Code> {
+        self.codes[..self.len].iter().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn code(&self, i: usize) -> Option<Code> {
+        if i < self.len {
+            self.codes[i]
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Checks that every conditional and repeater
+     * has a valid code following it
+     */
+    pub fn validate(&self) -> Result<(), CheatError> {
+        for i in 0..self.len {
+            let code = self.code(i).ok_or(CheatError::MissingTarget)?;
+
+            if code.kind.is_conditional() && self.code(i + 1).is_none() {
+                return Err(CheatError::MissingTarget);
+            }
+
+            if code.kind == CodeType::Repeat {
+                let next = self.code(i + 1).ok_or(CheatError::MissingTarget)?;
+                if !next.kind.is_write() {
+                    return Err(CheatError::InvalidRepeat);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// number of codes the code at i consumes
+    fn code_len(&self, i: usize) -> usize {
+        match self.code(i) {
+            Some(code) if code.kind == CodeType::Repeat => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.booted = false;
+    }
+
+    /**
+     * Runs all codes once
+     * a failing code stops the group for this frame
+     */
+    pub fn apply<M: MemoryAccess>(&mut self, memory: &M) -> Result<(), CheatError> {
+        if !self.active {
+            return Ok(());
+        }
+
+        let mut i = 0;
+        while let Some(code) = self.code(i) {
+            let mut next = i + self.code_len(i);
+
+            match code.kind {
+                CodeType::Write8 | CodeType::Write16 => {
+                    code.write(memory, code.addr, code.value)?
+                }
+                CodeType::Boot8 | CodeType::Boot16 => {
+                    if !self.booted {
+                        code.write(memory, code.addr, code.value)?;
+                    }
+                }
+                CodeType::Repeat => {
+                    let target = self.code(i + 1).ok_or(CheatError::MissingTarget)?;
+                    let (count, step, inc) = code.repeat();
+                    let mut addr = target.addr;
+                    let mut value = target.value;
+                    for _ in 0..count {
+                        target.write(memory, addr, value)?;
+                        addr += step;
+                        value = value.wrapping_add(inc);
+                    }
+                }
+                _ => {
+                    if !code.condition(memory)? {
+                        // skip the guarded code
+                        next += self.code_len(next);
+                    }
+                }
+            }
+            i = next;
+        }
+
+        self.booted = true;
+        Ok(())
+    }
+}
+
+/**
+ * Holds all cheat groups
+ * call apply once a frame from the overlay hook
+ */
+pub struct CheatEngine {
+    groups: [Option<CheatGroup>; MAX_GROUPS],
+    len: usize,
+}
+
+impl CheatEngine {
+    pub fn new() -> Self {
+        Self {
+            groups: [None; MAX_GROUPS],
+            len: 0,
+        }
+    }
+
+    pub fn add(&mut self, group: CheatGroup) -> Result<usize, CheatError> {
+        if self.len >= MAX_GROUPS {
+            return Err(CheatError::Full);
+        }
+        group.validate()?;
+        self.groups[self.len] = Some(group);
+        self.len += 1;
+        Ok(self.len - 1)
+    }
+
+    pub fn group(&self, i: usize) -> Option<&CheatGroup> {
+        self.groups.get(i)?.as_ref()
+    }
+
+    pub fn group_mut(&mut self, i: usize) -> Option<&mut CheatGroup> {
+        self.groups.get_mut(i)?.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn toggle(&mut self, i: usize) {
+        if let Some(group) = self.group_mut(i) {
+            group.toggle();
+        }
+    }
+
+    /**
+     * Applies all active groups
+     * errors of one group do not stop the others,
+     * the last error is returned
+     */
+    pub fn apply<M: MemoryAccess>(&mut self, memory: &M) -> Result<(), CheatError> {
+        let mut result = Ok(());
+        for group in self.groups.iter_mut().flatten() {
+            if let Err(err) = group.apply(memory) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
+
+impl Default for CheatEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * A menu that lists all cheat groups
+ * with a checkbox to toggle them
+ * The engine is shared with the overlay hook
+ * that applies the codes
+ */
+pub struct CheatMenu<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    cursor: usize,
+    pub active: bool,
+    toggle_timer_max: u16,
+    toggle_timer: u16,
+    close_action: EntryTypes<T>,
+    back_action: EntryTypes<T>,
+    pub engine: SharedPtrCell<CheatEngine>,
+}
+
+impl<T> CheatMenu<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(
+        x: isize,
+        y: isize,
+        engine: SharedPtrCell<CheatEngine>,
+        close_action: EntryTypes<T>,
+        back_action: EntryTypes<T>,
+    ) -> Self {
+        Self {
+            x,
+            y,
+            cursor: 0,
+            active: false,
+            toggle_timer_max: 10,
+            toggle_timer: 0,
+            close_action,
+            back_action,
+            engine,
+        }
+    }
+
+    pub fn inc_cursor(&mut self) {
+        self.cursor += 1;
+        if self.cursor >= self.engine.as_ref().len() {
+            self.cursor = 0;
+        }
+    }
+
+    pub fn dec_cursor(&mut self) {
+        if self.cursor == 0 {
+            self.cursor = self.engine.as_ref().len().saturating_sub(1);
+        } else {
+            self.cursor -= 1;
+        }
+    }
+
+    /// toggles the group under the cursor
+    pub fn activate(&mut self) {
+        if self.active {
+            self.engine.as_mut().toggle(self.cursor);
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.active = true;
+    }
+
+    pub fn close(&mut self, data: T) {
+        self.active = false;
+        self.close_action.activate(data);
+    }
+
+    pub fn back(&mut self, data: T) {
+        self.toggle_timer = 0;
+        self.back_action.activate(data);
+    }
+}
+
+impl<T> Drawable<T> for CheatMenu<T>
+where
+    T: Copy + Clone,
+{
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        let engine = self.engine.as_ref();
+        let mut line = [0u8; MAX_NAME_LEN + 3];
+        for i in 0..engine.len() {
+            let group = match engine.group(i) {
+                Some(group) => group,
+                None => continue,
+            };
+
+            line[0] = b'[';
+            line[1] = if group.active { b'x' } else { b' ' };
+            line[2] = b']';
+            line[3..].copy_from_slice(&group.name);
+
+            let y = self.y + i as isize * (ctxt.char_height() + 2);
+            if self.cursor == i && !ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF)) {
+                ctxt.puts(">", self.x, y);
+            }
+            ctxt.putsu8(&line, self.x + ctxt.char_width() + 2, y);
+        }
+    }
+
+    fn update(&mut self, _data: T) {
+        if self.toggle_timer > 0 {
+            self.toggle_timer -= 1;
+        }
+    }
+}
+
+impl<T> Widget<T> for CheatMenu<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, data: T) {
+        if self.toggle_timer > 0 {
+            return;
+        }
+
+        self.toggle_timer = self.toggle_timer_max;
+        if self.active {
+            self.close(data);
+        } else {
+            self.open();
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memmap::BufferMemory;
+
+    const RDRAM_LEN: usize = 0x100;
+
+    /// runs the codes once on a fresh buffer and returns it
+    fn run(codes: &str, setup: &[(usize, u8)]) -> [u8; RDRAM_LEN] {
+        let mut rdram = [0u8; RDRAM_LEN];
+        for (offset, value) in setup {
+            rdram[*offset] = *value;
+        }
+        {
+            let memory = BufferMemory::new(KSEG0, &mut rdram);
+            let mut group = CheatGroup::parse("test", codes).ok().unwrap();
+            group.toggle();
+            assert!(group.apply(&memory).is_ok());
+        }
+        rdram
+    }
+
+    #[test]
+    fn writes() {
+        let rdram = run("80000010 0012\n81000020 BEEF", &[]);
+        assert_eq!(rdram[0x10], 0x12);
+        assert_eq!(&rdram[0x20..0x22], &[0xBE, 0xEF]);
+
+        let rdram = run("A0000011 0034\nA1000022 1234", &[]);
+        assert_eq!(rdram[0x11], 0x34);
+        assert_eq!(&rdram[0x22..0x24], &[0x12, 0x34]);
+    }
+
+    #[test]
+    fn conditionals() {
+        let codes = "D0000030 0001;80000040 0001\n\
+                     D1000032 ABCD;80000041 0001\n\
+                     D2000030 0001;80000042 0001\n\
+                     D3000032 ABCD;80000043 0001";
+
+        let rdram = run(codes, &[]);
+        assert_eq!(&rdram[0x40..0x44], &[0, 0, 1, 1]);
+
+        let rdram = run(codes, &[(0x30, 0x01), (0x32, 0xAB), (0x33, 0xCD)]);
+        assert_eq!(&rdram[0x40..0x44], &[1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn conditional_skips_whole_repeater() {
+        let rdram = run("D0000030 0001\n50000201 0001\n80000050 0007", &[]);
+        assert_eq!(&rdram[0x50..0x52], &[0, 0]);
+    }
+
+    #[test]
+    fn repeater() {
+        let rdram = run("50000402 0001\n81000060 0010", &[]);
+        assert_eq!(
+            &rdram[0x60..0x68],
+            &[0x00, 0x10, 0x00, 0x11, 0x00, 0x12, 0x00, 0x13]
+        );
+    }
+
+    #[test]
+    fn boot_codes_run_once_per_activation() {
+        let mut rdram = [0u8; RDRAM_LEN];
+        let memory = BufferMemory::new(KSEG0, &mut rdram);
+        let mut group = CheatGroup::parse("boot", "F0000070 0033\nF1000072 4455")
+            .ok()
+            .unwrap();
+
+        group.toggle();
+        assert!(group.apply(&memory).is_ok());
+        assert_eq!(memory.read_u8(KSEG0 | 0x70).ok(), Some(0x33));
+        assert_eq!(memory.read_u16(KSEG0 | 0x72).ok(), Some(0x4455));
+
+        assert!(memory.write_u8(KSEG0 | 0x70, 0).is_ok());
+        assert!(group.apply(&memory).is_ok());
+        assert_eq!(memory.read_u8(KSEG0 | 0x70).ok(), Some(0));
+
+        group.toggle();
+        group.toggle();
+        assert!(group.apply(&memory).is_ok());
+        assert_eq!(memory.read_u8(KSEG0 | 0x70).ok(), Some(0x33));
+    }
+
+    #[test]
+    fn inactive_groups_do_nothing() {
+        let mut rdram = [0u8; RDRAM_LEN];
+        let memory = BufferMemory::new(KSEG0, &mut rdram);
+        let mut group = CheatGroup::parse("off", "80000010 0012").ok().unwrap();
+        assert!(group.apply(&memory).is_ok());
+        assert_eq!(memory.read_u8(KSEG0 | 0x10).ok(), Some(0));
+    }
+
+    #[test]
+    fn memory_errors_stop_the_group() {
+        let mut rdram = [0u8; RDRAM_LEN];
+        let memory = BufferMemory::new(KSEG0, &mut rdram);
+        let mut group = CheatGroup::parse("oob", "80100000 0001\n80000010 0012")
+            .ok()
+            .unwrap();
+        group.toggle();
+        assert!(group.apply(&memory) == Err(CheatError::Memory(MemoryError::Unmapped)));
+        assert_eq!(memory.read_u8(KSEG0 | 0x10).ok(), Some(0));
+    }
+
+    #[test]
+    fn invalid_codes() {
+        assert!(Code::parse(b"80000010 0105").err() == Some(CheatError::InvalidValue));
+        assert!(Code::parse(b"81000011 0105").err() == Some(CheatError::InvalidAddress));
+        assert!(Code::parse(b"12000011 0105").err() == Some(CheatError::UnknownType));
+        assert!(Code::parse(b"8000001 0105").err() == Some(CheatError::InvalidFormat));
+        assert!(Code::parse(b"50010402 0001").err() == Some(CheatError::UnknownType));
+        assert!(CheatGroup::parse("x", "D0000030 0001").is_err());
+        assert!(CheatGroup::parse("x", "50000402 0001\nD0000030 0001\n80000000 0000").is_err());
+    }
+}
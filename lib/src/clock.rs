@@ -0,0 +1,180 @@
+use crate::interrupt::get_count;
+use crate::text::TextWriter;
+use core::ptr::read_volatile;
+
+/// the count register runs at half of the 93.75MHz cpu clock
+pub const COUNT_HZ: u64 = 46_875_000;
+
+/// set by the boot code, 0 = PAL, 1 = NTSC, 2 = MPAL
+const OS_TV_TYPE: *const u32 = 0x80000300 as *const u32;
+
+/// number of half lines per field, set up by the game
+const VI_V_SYNC: *const u32 = 0xA4400018 as *const u32;
+const V_SYNC_NTSC: u32 = 525;
+const V_SYNC_PAL: u32 = 625;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum VideoStandard {
+    Ntsc,
+    Pal,
+    Mpal,
+}
+
+impl VideoStandard {
+    /**
+     * Uses the line count the VI is running at
+     * and falls back to osTvType if the VI is not set up yet
+     */
+    pub fn detect() -> Self {
+        let v_sync = unsafe { read_volatile(VI_V_SYNC) } & 0x3FF;
+        match v_sync {
+            V_SYNC_NTSC | 0x20C => return Self::Ntsc,
+            V_SYNC_PAL | 0x270 => return Self::Pal,
+            _ => {}
+        }
+
+        match unsafe { read_volatile(OS_TV_TYPE) } {
+            0 => Self::Pal,
+            2 => Self::Mpal,
+            _ => Self::Ntsc,
+        }
+    }
+
+    /// vertical interrupts per second
+    pub fn field_rate(&self) -> u32 {
+        match self {
+            Self::Ntsc | Self::Mpal => 60,
+            Self::Pal => 50,
+        }
+    }
+
+    /// exact vertical interrupts per 1000 seconds
+    pub fn field_rate_milli(&self) -> u64 {
+        match self {
+            Self::Ntsc | Self::Mpal => 59_940,
+            Self::Pal => 50_000,
+        }
+    }
+
+    /// the number of fields that fit into count register ticks * 1000
+    pub fn millifields(&self, ticks: u64) -> u64 {
+        ticks * self.field_rate_milli() / COUNT_HZ
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ntsc => "NTSC",
+            Self::Pal => "PAL",
+            Self::Mpal => "MPAL",
+        }
+    }
+}
+
+/**
+ * Measures real time using the cop0 count register
+ * The register wraps about every 91 seconds,
+ * so update has to be called at least that often
+ * (once a frame is plenty).
+ */
+#[derive(Copy, Clone)]
+pub struct Clock {
+    last: u32,
+    ticks: u64,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            last: Self::count(),
+            ticks: 0,
+        }
+    }
+
+    pub fn count() -> u32 {
+        unsafe { get_count() }
+    }
+
+    /// adds the time since the last call
+    pub fn update(&mut self) {
+        self.advance(Self::count());
+    }
+
+    /// adds the ticks up to count, handles the register wrapping
+    pub fn advance(&mut self, count: u32) {
+        self.ticks += count.wrapping_sub(self.last) as u64;
+        self.last = count;
+    }
+
+    /// time that passes while paused is not counted
+    pub fn resume(&mut self) {
+        self.last = Self::count();
+    }
+
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+        self.resume();
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn millis(&self) -> u64 {
+        self.ticks * 1000 / COUNT_HZ
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TimeFormat {
+    /// m:ss.mmm
+    Millis,
+    /// h:mm:ss
+    Hours,
+}
+
+impl TimeFormat {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Millis => Self::Hours,
+            Self::Hours => Self::Millis,
+        }
+    }
+}
+
+fn padded(value: u64, digits: u32, w: &mut TextWriter) {
+    let mut limit = 10;
+    for _ in 1..digits {
+        if value < limit {
+            w.push(b'0');
+        }
+        limit *= 10;
+    }
+    w.dec(value);
+}
+
+/// writes a time in milliseconds
+pub fn write_time(millis: u64, format: TimeFormat, w: &mut TextWriter) {
+    let seconds = millis / 1000;
+    match format {
+        TimeFormat::Millis => {
+            w.dec(seconds / 60);
+            w.push(b':');
+            padded(seconds % 60, 2, w);
+            w.push(b'.');
+            padded(millis % 1000, 3, w);
+        }
+        TimeFormat::Hours => {
+            w.dec(seconds / 3600);
+            w.push(b':');
+            padded(seconds / 60 % 60, 2, w);
+            w.push(b':');
+            padded(seconds % 60, 2, w);
+        }
+    }
+}
@@ -18,4 +18,12 @@ impl Color {
             alpha,
         }
     }
+
+    /// packs the color for a 16 bit framebuffer
+    pub fn to_rgba5551(&self) -> u16 {
+        ((self.red as u16 >> 3) << 11)
+            | ((self.green as u16 >> 3) << 6)
+            | ((self.blue as u16 >> 3) << 1)
+            | (self.alpha >> 7) as u16
+    }
 }
@@ -0,0 +1,690 @@
+use crate::text::TextWriter;
+
+/**
+ * A small no_std disassembler for the R4300i
+ * It decodes a single instruction word into
+ * a null terminated mnemonic string.
+ * Common pseudo-ops (nop, move, li, b, beqz, ...)
+ * are emitted instead of their raw encodings.
+ */
+
+pub const GPR_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+pub const COP0_NAMES: [&str; 32] = [
+    "Index", "Random", "EntryLo0", "EntryLo1", "Context", "PageMask", "Wired", "$7", "BadVAddr",
+    "Count", "EntryHi", "Compare", "Status", "Cause", "EPC", "PRId", "Config", "LLAddr", "WatchLo",
+    "WatchHi", "XContext", "$21", "$22", "$23", "$24", "$25", "PErr", "CacheErr", "TagLo", "TagHi",
+    "ErrorEPC", "$31",
+];
+
+const FPU_COMPARE_S: [&str; 16] = [
+    "c.f.s", "c.un.s", "c.eq.s", "c.ueq.s", "c.olt.s", "c.ult.s", "c.ole.s", "c.ule.s", "c.sf.s",
+    "c.ngle.s", "c.seq.s", "c.ngl.s", "c.lt.s", "c.nge.s", "c.le.s", "c.ngt.s",
+];
+
+const FPU_COMPARE_D: [&str; 16] = [
+    "c.f.d", "c.un.d", "c.eq.d", "c.ueq.d", "c.olt.d", "c.ult.d", "c.ole.d", "c.ule.d", "c.sf.d",
+    "c.ngle.d", "c.seq.d", "c.ngl.d", "c.lt.d", "c.nge.d", "c.le.d", "c.ngt.d",
+];
+
+/**
+ * Decoded fields of an instruction word
+ */
+#[derive(Copy, Clone)]
+pub struct Instruction {
+    pub addr: u32,
+    pub word: u32,
+}
+
+impl Instruction {
+    pub fn new(addr: u32, word: u32) -> Self {
+        Self { addr, word }
+    }
+
+    pub fn op(&self) -> u32 {
+        self.word >> 26
+    }
+
+    pub fn rs(&self) -> usize {
+        ((self.word >> 21) & 0x1F) as usize
+    }
+
+    pub fn rt(&self) -> usize {
+        ((self.word >> 16) & 0x1F) as usize
+    }
+
+    pub fn rd(&self) -> usize {
+        ((self.word >> 11) & 0x1F) as usize
+    }
+
+    pub fn sa(&self) -> u32 {
+        (self.word >> 6) & 0x1F
+    }
+
+    pub fn funct(&self) -> u32 {
+        self.word & 0x3F
+    }
+
+    pub fn imm(&self) -> u16 {
+        self.word as u16
+    }
+
+    pub fn simm(&self) -> i16 {
+        self.word as i16
+    }
+
+    /// target of a pc relative branch
+    pub fn branch_target(&self) -> u32 {
+        self.addr
+            .wrapping_add(4)
+            .wrapping_add(((self.simm() as i32) << 2) as u32)
+    }
+
+    /// target of a j or jal instruction
+    pub fn jump_target(&self) -> u32 {
+        (self.addr.wrapping_add(4) & 0xF0000000) | ((self.word & 0x03FFFFFF) << 2)
+    }
+
+    /**
+     * Target address of a jump or branch
+     * None for register jumps and everything else
+     */
+    pub fn target(&self) -> Option<u32> {
+        match self.op() {
+            0x02 | 0x03 => Some(self.jump_target()),
+            0x04..=0x07 | 0x14..=0x17 => Some(self.branch_target()),
+            0x01 if (self.rt() & 0x0C) == 0 => Some(self.branch_target()),
+            0x11 if self.rs() == 0x08 => Some(self.branch_target()),
+            _ => None,
+        }
+    }
+
+    /// fpu registers use the rd, rt and sa fields
+    pub fn fs(&self) -> usize {
+        self.rd()
+    }
+
+    pub fn ft(&self) -> usize {
+        self.rt()
+    }
+
+    pub fn fd(&self) -> usize {
+        self.sa() as usize
+    }
+}
+
+/**
+ * Operand layouts used by the formatter
+ */
+enum Operands {
+    Empty,
+    /// rd, rs, rt
+    RdRsRt,
+    /// rd, rt, rs (variable shifts)
+    RdRtRs,
+    /// rd, rt (negu)
+    RdRt,
+    /// rd, rt, sa
+    RdRtSa,
+    /// rs, rt (mult, div, traps)
+    RsRt,
+    Rs,
+    Rd,
+    /// rd, rs (jalr)
+    RdRs,
+    /// rt, rs, imm
+    RtRsSimm,
+    RtRsImm,
+    /// rt, imm
+    RtImm,
+    /// rs, rt, branch target
+    RsRtBranch,
+    /// rs, branch target
+    RsBranch,
+    Branch,
+    Jump,
+    /// rt, offset(base)
+    RtMem,
+    /// ft, offset(base)
+    FtMem,
+    /// rs, imm (trap immediate)
+    RsSimm,
+    /// rt, cop0 register
+    RtCop0,
+    /// rt, fs
+    RtFs,
+    /// fd, fs, ft
+    FdFsFt,
+    /// fd, fs
+    FdFs,
+    /// fs, ft
+    FsFt,
+    /// cache op, offset(base)
+    CacheMem,
+    /// 20 bit code field of syscall and break
+    Code,
+}
+
+fn fpr(w: &mut TextWriter, r: usize) {
+    w.push(b'f');
+    w.dec(r as u64);
+}
+
+/**
+ * Disassembles a single word located at addr
+ * into buffer. The result is null terminated and truncated
+ * to fit the buffer.
+ * Returns the length of the string without terminator.
+ */
+pub fn disassemble(addr: u32, word: u32, buffer: &mut [u8]) -> usize {
+    let i = Instruction::new(addr, word);
+    let mut w = TextWriter::new(buffer);
+
+    if let Some((mnemonic, operands)) = pseudo_op(&i).or_else(|| decode(&i)) {
+        w.puts(mnemonic);
+        write_operands(&mut w, &i, operands);
+    } else {
+        w.puts(".word ");
+        w.hex_short(word as u64);
+    }
+
+    w.finish()
+}
+
+/**
+ * Recognizes common assembler pseudo instructions
+ */
+fn pseudo_op(i: &Instruction) -> Option<(&'static str, Operands)> {
+    if i.word == 0 {
+        return Some(("nop", Operands::Empty));
+    }
+
+    match i.op() {
+        0 => match i.funct() {
+            // addu, or, daddu with zero
+            0x21 | 0x25 | 0x2D if i.rt() == 0 => Some(("move", Operands::RdRs)),
+            0x23 if i.rs() == 0 => Some(("negu", Operands::RdRt)),
+            0x27 if i.rt() == 0 => Some(("not", Operands::RdRs)),
+            _ => None,
+        },
+        1 if i.rt() == 0x11 && i.rs() == 0 => Some(("bal", Operands::Branch)),
+        4 if i.rs() == 0 && i.rt() == 0 => Some(("b", Operands::Branch)),
+        4 if i.rt() == 0 => Some(("beqz", Operands::RsBranch)),
+        5 if i.rt() == 0 => Some(("bnez", Operands::RsBranch)),
+        9 if i.rs() == 0 => Some(("li", Operands::RtImm)),
+        0xD if i.rs() == 0 => Some(("li", Operands::RtImm)),
+        _ => None,
+    }
+}
+
+fn decode(i: &Instruction) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let decoded = match i.op() {
+        0x00 => return decode_special(i),
+        0x01 => return decode_regimm(i),
+        0x02 => ("j", Jump),
+        0x03 => ("jal", Jump),
+        0x04 => ("beq", RsRtBranch),
+        0x05 => ("bne", RsRtBranch),
+        0x06 => ("blez", RsBranch),
+        0x07 => ("bgtz", RsBranch),
+        0x08 => ("addi", RtRsSimm),
+        0x09 => ("addiu", RtRsSimm),
+        0x0A => ("slti", RtRsSimm),
+        0x0B => ("sltiu", RtRsSimm),
+        0x0C => ("andi", RtRsImm),
+        0x0D => ("ori", RtRsImm),
+        0x0E => ("xori", RtRsImm),
+        0x0F => ("lui", RtImm),
+        0x10 => return decode_cop0(i),
+        0x11 => return decode_cop1(i),
+        0x14 => ("beql", RsRtBranch),
+        0x15 => ("bnel", RsRtBranch),
+        0x16 => ("blezl", RsBranch),
+        0x17 => ("bgtzl", RsBranch),
+        0x18 => ("daddi", RtRsSimm),
+        0x19 => ("daddiu", RtRsSimm),
+        0x1A => ("ldl", RtMem),
+        0x1B => ("ldr", RtMem),
+        0x20 => ("lb", RtMem),
+        0x21 => ("lh", RtMem),
+        0x22 => ("lwl", RtMem),
+        0x23 => ("lw", RtMem),
+        0x24 => ("lbu", RtMem),
+        0x25 => ("lhu", RtMem),
+        0x26 => ("lwr", RtMem),
+        0x27 => ("lwu", RtMem),
+        0x28 => ("sb", RtMem),
+        0x29 => ("sh", RtMem),
+        0x2A => ("swl", RtMem),
+        0x2B => ("sw", RtMem),
+        0x2C => ("sdl", RtMem),
+        0x2D => ("sdr", RtMem),
+        0x2E => ("swr", RtMem),
+        0x2F => ("cache", CacheMem),
+        0x30 => ("ll", RtMem),
+        0x31 => ("lwc1", FtMem),
+        0x34 => ("lld", RtMem),
+        0x35 => ("ldc1", FtMem),
+        0x37 => ("ld", RtMem),
+        0x38 => ("sc", RtMem),
+        0x39 => ("swc1", FtMem),
+        0x3C => ("scd", RtMem),
+        0x3D => ("sdc1", FtMem),
+        0x3F => ("sd", RtMem),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+fn decode_special(i: &Instruction) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let decoded = match i.funct() {
+        0x00 => ("sll", RdRtSa),
+        0x02 => ("srl", RdRtSa),
+        0x03 => ("sra", RdRtSa),
+        0x04 => ("sllv", RdRtRs),
+        0x06 => ("srlv", RdRtRs),
+        0x07 => ("srav", RdRtRs),
+        0x08 => ("jr", Rs),
+        0x09 if i.rd() == 31 => ("jalr", Rs),
+        0x09 => ("jalr", RdRs),
+        0x0C => ("syscall", Code),
+        0x0D => ("break", Code),
+        0x0F => ("sync", Empty),
+        0x10 => ("mfhi", Rd),
+        0x11 => ("mthi", Rs),
+        0x12 => ("mflo", Rd),
+        0x13 => ("mtlo", Rs),
+        0x14 => ("dsllv", RdRtRs),
+        0x16 => ("dsrlv", RdRtRs),
+        0x17 => ("dsrav", RdRtRs),
+        0x18 => ("mult", RsRt),
+        0x19 => ("multu", RsRt),
+        0x1A => ("div", RsRt),
+        0x1B => ("divu", RsRt),
+        0x1C => ("dmult", RsRt),
+        0x1D => ("dmultu", RsRt),
+        0x1E => ("ddiv", RsRt),
+        0x1F => ("ddivu", RsRt),
+        0x20 => ("add", RdRsRt),
+        0x21 => ("addu", RdRsRt),
+        0x22 => ("sub", RdRsRt),
+        0x23 => ("subu", RdRsRt),
+        0x24 => ("and", RdRsRt),
+        0x25 => ("or", RdRsRt),
+        0x26 => ("xor", RdRsRt),
+        0x27 => ("nor", RdRsRt),
+        0x2A => ("slt", RdRsRt),
+        0x2B => ("sltu", RdRsRt),
+        0x2C => ("dadd", RdRsRt),
+        0x2D => ("daddu", RdRsRt),
+        0x2E => ("dsub", RdRsRt),
+        0x2F => ("dsubu", RdRsRt),
+        0x30 => ("tge", RsRt),
+        0x31 => ("tgeu", RsRt),
+        0x32 => ("tlt", RsRt),
+        0x33 => ("tltu", RsRt),
+        0x34 => ("teq", RsRt),
+        0x36 => ("tne", RsRt),
+        0x38 => ("dsll", RdRtSa),
+        0x3A => ("dsrl", RdRtSa),
+        0x3B => ("dsra", RdRtSa),
+        0x3C => ("dsll32", RdRtSa),
+        0x3E => ("dsrl32", RdRtSa),
+        0x3F => ("dsra32", RdRtSa),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+fn decode_regimm(i: &Instruction) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let decoded = match i.rt() {
+        0x00 => ("bltz", RsBranch),
+        0x01 => ("bgez", RsBranch),
+        0x02 => ("bltzl", RsBranch),
+        0x03 => ("bgezl", RsBranch),
+        0x08 => ("tgei", RsSimm),
+        0x09 => ("tgeiu", RsSimm),
+        0x0A => ("tlti", RsSimm),
+        0x0B => ("tltiu", RsSimm),
+        0x0C => ("teqi", RsSimm),
+        0x0E => ("tnei", RsSimm),
+        0x10 => ("bltzal", RsBranch),
+        0x11 => ("bgezal", RsBranch),
+        0x12 => ("bltzall", RsBranch),
+        0x13 => ("bgezall", RsBranch),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+fn decode_cop0(i: &Instruction) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let decoded = match i.rs() {
+        0x00 => ("mfc0", RtCop0),
+        0x01 => ("dmfc0", RtCop0),
+        0x04 => ("mtc0", RtCop0),
+        0x05 => ("dmtc0", RtCop0),
+        0x10 => match i.funct() {
+            0x01 => ("tlbr", Empty),
+            0x02 => ("tlbwi", Empty),
+            0x06 => ("tlbwr", Empty),
+            0x08 => ("tlbp", Empty),
+            0x18 => ("eret", Empty),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+fn decode_cop1(i: &Instruction) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let decoded = match i.rs() {
+        0x00 => ("mfc1", RtFs),
+        0x01 => ("dmfc1", RtFs),
+        0x02 => ("cfc1", RtFs),
+        0x04 => ("mtc1", RtFs),
+        0x05 => ("dmtc1", RtFs),
+        0x06 => ("ctc1", RtFs),
+        0x08 => match i.rt() {
+            0x00 => ("bc1f", Branch),
+            0x01 => ("bc1t", Branch),
+            0x02 => ("bc1fl", Branch),
+            0x03 => ("bc1tl", Branch),
+            _ => return None,
+        },
+        0x10 => return decode_fpu_op(i, FpuFormat::S),
+        0x11 => return decode_fpu_op(i, FpuFormat::D),
+        0x14 => return decode_fpu_op(i, FpuFormat::W),
+        0x15 => return decode_fpu_op(i, FpuFormat::L),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum FpuFormat {
+    S,
+    D,
+    W,
+    L,
+}
+
+/**
+ * Since the mnemonic has to be a static str
+ * every format variant is spelled out
+ */
+macro_rules! fpu_name {
+    ($fmt:expr, $name:literal) => {
+        match $fmt {
+            FpuFormat::S => concat!($name, ".s"),
+            FpuFormat::D => concat!($name, ".d"),
+            FpuFormat::W => concat!($name, ".w"),
+            FpuFormat::L => concat!($name, ".l"),
+        }
+    };
+}
+
+fn decode_fpu_op(i: &Instruction, fmt: FpuFormat) -> Option<(&'static str, Operands)> {
+    use Operands::*;
+
+    let is_float = fmt == FpuFormat::S || fmt == FpuFormat::D;
+    let funct = i.funct();
+
+    // conversions are the only valid operations on fixed point formats
+    if !is_float && !(0x20..=0x21).contains(&funct) {
+        return None;
+    }
+
+    if funct >= 0x30 {
+        let name = if fmt == FpuFormat::S {
+            FPU_COMPARE_S[(funct & 0xF) as usize]
+        } else {
+            FPU_COMPARE_D[(funct & 0xF) as usize]
+        };
+        return Some((name, FsFt));
+    }
+
+    let decoded = match funct {
+        0x00 => (fpu_name!(fmt, "add"), FdFsFt),
+        0x01 => (fpu_name!(fmt, "sub"), FdFsFt),
+        0x02 => (fpu_name!(fmt, "mul"), FdFsFt),
+        0x03 => (fpu_name!(fmt, "div"), FdFsFt),
+        0x04 => (fpu_name!(fmt, "sqrt"), FdFs),
+        0x05 => (fpu_name!(fmt, "abs"), FdFs),
+        0x06 => (fpu_name!(fmt, "mov"), FdFs),
+        0x07 => (fpu_name!(fmt, "neg"), FdFs),
+        0x08 => (fpu_name!(fmt, "round.l"), FdFs),
+        0x09 => (fpu_name!(fmt, "trunc.l"), FdFs),
+        0x0A => (fpu_name!(fmt, "ceil.l"), FdFs),
+        0x0B => (fpu_name!(fmt, "floor.l"), FdFs),
+        0x0C => (fpu_name!(fmt, "round.w"), FdFs),
+        0x0D => (fpu_name!(fmt, "trunc.w"), FdFs),
+        0x0E => (fpu_name!(fmt, "ceil.w"), FdFs),
+        0x0F => (fpu_name!(fmt, "floor.w"), FdFs),
+        0x20 if fmt != FpuFormat::S => (fpu_name!(fmt, "cvt.s"), FdFs),
+        0x21 if fmt != FpuFormat::D => (fpu_name!(fmt, "cvt.d"), FdFs),
+        0x24 if is_float => (fpu_name!(fmt, "cvt.w"), FdFs),
+        0x25 if is_float => (fpu_name!(fmt, "cvt.l"), FdFs),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+fn write_operands(w: &mut TextWriter, i: &Instruction, operands: Operands) {
+    if let Operands::Empty = operands {
+        return;
+    }
+
+    w.push(b' ');
+    match operands {
+        Operands::Empty => {}
+        Operands::RdRsRt => {
+            w.puts(GPR_NAMES[i.rd()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+        }
+        Operands::RdRtRs => {
+            w.puts(GPR_NAMES[i.rd()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rs()]);
+        }
+        Operands::RdRt => {
+            w.puts(GPR_NAMES[i.rd()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+        }
+        Operands::RdRtSa => {
+            w.puts(GPR_NAMES[i.rd()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.hex_short(i.sa() as u64);
+        }
+        Operands::RsRt => {
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+        }
+        Operands::Rs => w.puts(GPR_NAMES[i.rs()]),
+        Operands::Rd => w.puts(GPR_NAMES[i.rd()]),
+        Operands::RdRs => {
+            w.puts(GPR_NAMES[i.rd()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rs()]);
+        }
+        Operands::RtRsSimm => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.signed_hex(i.simm() as i64);
+        }
+        Operands::RtRsImm => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.hex_short(i.imm() as u64);
+        }
+        Operands::RtImm => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            // li via addiu sign extends
+            if i.op() == 0x09 {
+                w.signed_hex(i.simm() as i64);
+            } else {
+                w.hex_short(i.imm() as u64);
+            }
+        }
+        Operands::RsRtBranch => {
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.hex(i.branch_target() as u64, 8);
+        }
+        Operands::RsBranch => {
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.hex(i.branch_target() as u64, 8);
+        }
+        Operands::Branch => w.hex(i.branch_target() as u64, 8),
+        Operands::Jump => w.hex(i.jump_target() as u64, 8),
+        Operands::RtMem => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            write_mem(w, i);
+        }
+        Operands::FtMem => {
+            fpr(w, i.ft());
+            w.push(b',');
+            write_mem(w, i);
+        }
+        Operands::RsSimm => {
+            w.puts(GPR_NAMES[i.rs()]);
+            w.push(b',');
+            w.signed_hex(i.simm() as i64);
+        }
+        Operands::RtCop0 => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            w.puts(COP0_NAMES[i.rd()]);
+        }
+        Operands::RtFs => {
+            w.puts(GPR_NAMES[i.rt()]);
+            w.push(b',');
+            fpr(w, i.fs());
+        }
+        Operands::FdFsFt => {
+            fpr(w, i.fd());
+            w.push(b',');
+            fpr(w, i.fs());
+            w.push(b',');
+            fpr(w, i.ft());
+        }
+        Operands::FdFs => {
+            fpr(w, i.fd());
+            w.push(b',');
+            fpr(w, i.fs());
+        }
+        Operands::FsFt => {
+            fpr(w, i.fs());
+            w.push(b',');
+            fpr(w, i.ft());
+        }
+        Operands::CacheMem => {
+            w.hex_short(i.rt() as u64);
+            w.push(b',');
+            write_mem(w, i);
+        }
+        Operands::Code => w.hex_short(((i.word >> 6) & 0xFFFFF) as u64),
+    }
+}
+
+fn write_mem(w: &mut TextWriter, i: &Instruction) {
+    w.signed_hex(i.simm() as i64);
+    w.push(b'(');
+    w.puts(GPR_NAMES[i.rs()]);
+    w.push(b')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(addr: u32, word: u32) -> String {
+        let mut buffer = [0u8; 48];
+        let len = disassemble(addr, word, &mut buffer);
+        String::from_utf8(buffer[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn known_encodings() {
+        let table: &[(u32, &str)] = &[
+            // pseudo-ops
+            (0x00000000, "nop"),
+            (0x00801025, "move v0,a0"),
+            (0x24020005, "li v0,0x5"),
+            (0x1000FFFF, "b 80000000"),
+            (0x10400003, "beqz v0,80000010"),
+            (0x14A00002, "bnez a1,8000000C"),
+            (0x04110002, "bal 8000000C"),
+            // integer
+            (0x03E00008, "jr ra"),
+            (0x0C100000, "jal 80400000"),
+            (0x8FBF0014, "lw ra,0x14(sp)"),
+            (0x27BDFFE8, "addiu sp,sp,-0x18"),
+            (0x3C018040, "lui at,0x8040"),
+            (0x0005102B, "sltu v0,zero,a1"),
+            (0x0000000D, "break 0x0"),
+            // cop0
+            (0x40086800, "mfc0 t0,Cause"),
+            (0x40887000, "mtc0 t0,EPC"),
+            (0x42000018, "eret"),
+            // cop1
+            (0x44821000, "mtc1 v0,f2"),
+            (0x46022000, "add.s f0,f4,f2"),
+            (0x46000806, "mov.s f0,f1"),
+            (0x4600008D, "trunc.w.s f2,f0"),
+            (0x45000004, "bc1f 80000014"),
+            // unknown opcodes
+            (0x7C000000, ".word 0x7C000000"),
+            (0xEC000000, ".word 0xEC000000"),
+        ];
+
+        for (word, expected) in table {
+            assert_eq!(text(0x80000000, *word), *expected, "{:08X}", word);
+        }
+    }
+
+    #[test]
+    fn truncates_to_buffer() {
+        let mut buffer = [0xFFu8; 4];
+        let len = disassemble(0x80000000, 0x03E00008, &mut buffer);
+        assert_eq!(len, 3);
+        assert_eq!(&buffer, b"jr \0");
+    }
+}
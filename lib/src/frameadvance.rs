@@ -1,8 +1,104 @@
-use crate::menu::EntryTypes;
+use crate::clock::{Clock, VideoStandard, COUNT_HZ};
+use crate::input::{Button, InputHandler};
+use crate::menu::{no_op, Entry, EntryFn, EntryTypes};
 use crate::render::Drawable;
 use crate::render::RenderContext;
 use crate::render::Widget;
+use crate::si::Controllers;
+use crate::text::TextWriter;
 
+/**
+ * Called every iteration while the game is halted
+ * It should draw the overlay and service usb,
+ * the game does not do either until it runs again.
+ */
+pub type ServiceFn<T> = fn(data: T);
+
+/**
+ * Called after the game was halted or slowed down, before it runs again
+ * Anything that measures the game's speed should skip the held time here,
+ * e.g. LagCounter::resume.
+ */
+pub type ResumeFn<T> = fn(data: T);
+
+fn resume_no_op<T: Copy + Clone>(_data: T) {}
+
+/**
+ * How fast the game runs
+ * The game still runs every frame, each frame is held
+ * until it took divider times its normal length.
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub enum Speed {
+    Full = 1,
+    Half = 2,
+    Quarter = 4,
+    Eighth = 8,
+}
+
+impl Speed {
+    /// every frame takes this many times as long
+    pub fn divider(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Full => Self::Half,
+            Self::Half => Self::Quarter,
+            Self::Quarter => Self::Eighth,
+            Self::Eighth => Self::Full,
+        }
+    }
+
+    /// menu entry title
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Full => "Speed 1/1",
+            Self::Half => "Speed 1/2",
+            Self::Quarter => "Speed 1/4",
+            Self::Eighth => "Speed 1/8",
+        }
+    }
+}
+
+/**
+ * A menu entry for the speed selector
+ * The action should cycle the speed and update the title:
+ * let speed = frame_advance.cycle_speed();
+ * entry.set_title(speed.title());
+ */
+pub fn speed_entry<T: Copy + Clone>(action: EntryFn<T>) -> EntryTypes<T> {
+    Entry::new(Speed::Full.title(), no_op, action)
+}
+
+#[derive(Copy, Clone)]
+pub struct FrameAdvanceButtons {
+    /// pauses and resumes the game
+    pub pause: Button,
+    /// runs a single frame
+    pub step: Button,
+    /// runs step_frames frames
+    pub step_n: Button,
+}
+
+impl Default for FrameAdvanceButtons {
+    fn default() -> Self {
+        Self {
+            pause: Button::DpadUp,
+            step: Button::DpadRight,
+            step_n: Button::DpadLeft,
+        }
+    }
+}
+
+/**
+ * Halts the game between frames
+ * hold has to be called from the game's frame hook,
+ * while paused it does not return until the user steps or resumes.
+ * The game does not read its controllers while halted,
+ * so the controller of port is read through the si instead.
+ */
 pub struct FrameAdvance<T>
 where
     T: Copy + Clone,
@@ -13,29 +109,177 @@ where
     toggle_timer: u16,
     active: bool,
     close_action: EntryTypes<T>,
+    service: ServiceFn<T>,
+    resumed: ResumeFn<T>,
+    controllers: Controllers,
+    /// the controller that is read while the game is halted, 0 to 3
+    pub port: usize,
+    pub buttons: FrameAdvanceButtons,
+    pub paused: bool,
+    /// frames that ran since frame advance was opened
+    pub frame: u32,
+    /// frames the step_n button runs
+    pub step_frames: u32,
+    /// frames left to run before pausing again
+    remaining: u32,
+    pub speed: Speed,
+    /// count when the game's last frame started
+    frame_start: u32,
+    standard: VideoStandard,
 }
 
 impl<T> FrameAdvance<T>
 where
     T: Copy + Clone,
 {
-    pub fn new(x: isize, y: isize, close_action: EntryTypes<T>) -> Self {
+    pub fn new(x: isize, y: isize, close_action: EntryTypes<T>, service: ServiceFn<T>) -> Self {
         Self {
             active: true,
             toggle_timer_max: 10,
             toggle_timer: 0,
             close_action,
+            service,
+            resumed: resume_no_op,
+            controllers: Controllers::new(),
+            port: 0,
+            buttons: FrameAdvanceButtons::default(),
+            paused: false,
+            frame: 0,
+            step_frames: 10,
+            remaining: 0,
+            speed: Speed::Full,
+            frame_start: Clock::count(),
+            standard: VideoStandard::detect(),
             x,
             y,
         }
     }
-    pub fn open(&mut self, data: T) {
+
+    pub fn with_resume(mut self, resumed: ResumeFn<T>) -> Self {
+        self.resumed = resumed;
+        self
+    }
+
+    pub fn open(&mut self, _data: T) {
         self.active = true;
+        self.frame = 0;
     }
 
     pub fn close(&mut self, data: T) {
         self.close_action.activate(data);
         self.active = false;
+        self.resume();
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.remaining = 0;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.remaining = 0;
+    }
+
+    /// lets frames run and pauses again afterwards
+    pub fn step(&mut self, frames: u32) {
+        self.paused = false;
+        self.remaining = frames;
+    }
+
+    pub fn cycle_speed(&mut self) -> Speed {
+        self.speed = self.speed.next();
+        self.speed
+    }
+
+    /// waits for about one vertical interrupt
+    fn wait_field(&self) {
+        let ticks = (COUNT_HZ * 1000 / self.standard.field_rate_milli()) as u32;
+        let start = Clock::count();
+        while Clock::count().wrapping_sub(start) < ticks {}
+    }
+
+    /// reads the buttons, returns true if the game may run again
+    fn poll(&mut self, input: &InputHandler) -> bool {
+        if input.read_button(self.buttons.pause, true) {
+            self.resume();
+        } else if input.read_button(self.buttons.step, true) {
+            self.step(1);
+        } else if input.read_button(self.buttons.step_n, true) {
+            self.step(self.step_frames);
+        }
+        !self.paused
+    }
+
+    /**
+     * Call once a frame from the game's frame hook
+     * after the input handler was updated.
+     * Spins until the game may run the next frame.
+     */
+    pub fn hold(&mut self, data: T, input: &mut InputHandler) {
+        if !self.active {
+            return;
+        }
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.paused = true;
+            }
+        } else if !self.paused && input.read_button(self.buttons.pause, true) {
+            self.pause();
+        }
+
+        let held = self.paused || self.speed != Speed::Full;
+        self.hold_paused(data, input);
+
+        if !self.paused && self.speed != Speed::Full {
+            self.slow_down(data, input);
+        }
+
+        if held {
+            (self.resumed)(data);
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+        self.frame_start = Clock::count();
+    }
+
+    /// pif ram is not refreshed while the game is halted, the controller is polled here
+    fn service(&mut self, data: T, input: &mut InputHandler) {
+        (self.service)(data);
+        self.controllers.poll();
+        input.update_from(self.controllers.ports[self.port].status);
+    }
+
+    /**
+     * Stretches the frame that just ran to speed.divider() times its length.
+     * Pausing still works while waiting, the service fn runs once a field.
+     */
+    fn slow_down(&mut self, data: T, input: &mut InputHandler) {
+        let start = Clock::count();
+        let frame = start.wrapping_sub(self.frame_start);
+        let wait = frame.saturating_mul(self.speed.divider() - 1);
+
+        while Clock::count().wrapping_sub(start) < wait {
+            self.service(data, input);
+            if input.read_button(self.buttons.pause, true) {
+                self.pause();
+                return self.hold_paused(data, input);
+            }
+            self.wait_field();
+        }
+    }
+
+    /// spins until the user resumes or steps
+    fn hold_paused(&mut self, data: T, input: &mut InputHandler) {
+        while self.paused {
+            self.service(data, input);
+            if self.poll(input) {
+                break;
+            }
+            self.wait_field();
+        }
     }
 }
 
@@ -69,15 +313,24 @@ where
         if !self.active {
             return;
         }
-        ctxt.puts("Frame Advance", self.x, self.y);
+
+        let mut line = [0u8; 32];
+        let mut w = TextWriter::new(&mut line);
+        w.puts("Frame ");
+        w.dec(self.frame as u64);
+        if self.paused {
+            w.puts(" paused");
+        } else if self.speed != Speed::Full {
+            w.puts(" 1/");
+            w.dec(self.speed.divider() as u64);
+        }
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y);
     }
 
-    fn update(&mut self, data: T) {
+    fn update(&mut self, _data: T) {
         if self.toggle_timer > 0 {
             self.toggle_timer -= 1;
         }
-        if !self.active {
-            return;
-        }
     }
 }
@@ -0,0 +1,157 @@
+use crate::color::Color;
+use crate::render::RenderContext;
+
+/// the most samples a history can hold, 2 seconds at 60 fps
+pub const MAX_HISTORY: usize = 120;
+
+/// height of a graph in pixels
+pub const GRAPH_HEIGHT: isize = 20;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GraphStyle {
+    Sparkline,
+    /// needs a render context that can fill rectangles
+    /// otherwise it is drawn as a sparkline
+    Bars,
+}
+
+/**
+ * Ring buffer of the last capacity samples
+ * once it is full the oldest sample is overwritten
+ */
+#[derive(Copy, Clone)]
+pub struct History {
+    values: [f32; MAX_HISTORY],
+    head: usize,
+    len: usize,
+    capacity: usize,
+    pub style: GraphStyle,
+}
+
+impl History {
+    pub fn new(capacity: usize, style: GraphStyle) -> Self {
+        Self {
+            values: [0.0; MAX_HISTORY],
+            head: 0,
+            len: 0,
+            capacity: capacity.clamp(1, MAX_HISTORY),
+            style,
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.values[self.head] = value;
+        self.head = (self.head + 1) % self.capacity;
+        if self.len < self.capacity {
+            self.len += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// the sample at index, 0 is the oldest
+    pub fn get(&self, index: usize) -> Option<f32> {
+        if index >= self.len {
+            return None;
+        }
+
+        let start = (self.head + self.capacity - self.len) % self.capacity;
+        Some(self.values[(start + index) % self.capacity])
+    }
+
+    /// samples from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+
+    /// index and value of the smallest sample
+    pub fn min(&self) -> Option<(usize, f32)> {
+        self.iter()
+            .enumerate()
+            .filter(|(_, v)| !v.is_nan())
+            .fold(None, |min, (i, v)| match min {
+                Some((_, m)) if m <= v => min,
+                _ => Some((i, v)),
+            })
+    }
+
+    /// index and value of the largest sample
+    pub fn max(&self) -> Option<(usize, f32)> {
+        self.iter()
+            .enumerate()
+            .filter(|(_, v)| !v.is_nan())
+            .fold(None, |max, (i, v)| match max {
+                Some((_, m)) if m >= v => max,
+                _ => Some((i, v)),
+            })
+    }
+
+    /**
+     * Draws the samples with one pixel per sample
+     * scaled between the smallest and largest value.
+     * The smallest sample is marked blue and the largest red.
+     * Returns the width of the graph in pixels.
+     */
+    pub fn draw(&self, ctxt: &mut dyn RenderContext, x: isize, y: isize) -> isize {
+        let (min, max) = match (self.min(), self.max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return 0,
+        };
+
+        let range = max.1 - min.1;
+        let bottom = y + GRAPH_HEIGHT;
+        let scale = |v: f32| -> isize {
+            if range > 0.0 && range.is_finite() {
+                ((v - min.1) / range * (GRAPH_HEIGHT - 1) as f32) as isize
+            } else {
+                GRAPH_HEIGHT / 2
+            }
+        };
+
+        // characters are drawn from their top left corner
+        // so the point is moved up by half a character
+        let point_offset = ctxt.char_height() / 2;
+        for (i, v) in self.iter().enumerate() {
+            if v.is_nan() {
+                continue;
+            }
+
+            let px = x + i as isize;
+            let height = scale(v);
+            let color = if i == max.0 {
+                Color::new(0xFF, 0x00, 0x00, 0xFF)
+            } else if i == min.0 {
+                Color::new(0x00, 0x80, 0xFF, 0xFF)
+            } else {
+                Color::new(0x00, 0xFF, 0x00, 0xFF)
+            };
+
+            let filled = match self.style {
+                GraphStyle::Bars => ctxt.fill_rect(px, bottom - height - 1, 1, height + 1, color),
+                GraphStyle::Sparkline => ctxt.fill_rect(px, bottom - height - 1, 1, 1, color),
+            };
+
+            if !filled {
+                ctxt.set_color(color);
+                ctxt.puts(".", px, bottom - height - point_offset);
+            }
+        }
+
+        self.capacity as isize
+    }
+}
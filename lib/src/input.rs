@@ -1,3 +1,5 @@
+use crate::math::{angle, isqrt};
+
 /**
  * Describes all buttons on the n64 controller
  */
@@ -10,7 +12,7 @@ pub enum Button {
     CRInput = 0x10,
     CDInput = 0x12,
     CUInput = 0x13,
-    AInput = 0xFF,
+    AInput = 0x1F,
     BInput = 0x1E,
     StartInput = 0x1C,
     DpadUp = 0x1B,
@@ -19,17 +21,128 @@ pub enum Button {
     DpadLeft = 0x19,
 }
 
+impl Button {
+    /// every button in the order of the controller status bits
+    pub const ALL: [Button; 14] = [
+        Button::AInput,
+        Button::BInput,
+        Button::ZInput,
+        Button::StartInput,
+        Button::DpadUp,
+        Button::DpadDown,
+        Button::DpadLeft,
+        Button::DpadRight,
+        Button::LInput,
+        Button::RInput,
+        Button::CUInput,
+        Button::CDInput,
+        Button::CLInput,
+        Button::CRInput,
+    ];
+
+    /// short label for displays
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::AInput => "A",
+            Button::BInput => "B",
+            Button::ZInput => "Z",
+            Button::StartInput => "S",
+            Button::DpadUp => "^",
+            Button::DpadDown => "v",
+            Button::DpadLeft => "<",
+            Button::DpadRight => ">",
+            Button::LInput => "L",
+            Button::RInput => "R",
+            Button::CUInput => "C^",
+            Button::CDInput => "Cv",
+            Button::CLInput => "C<",
+            Button::CRInput => "C>",
+        }
+    }
+}
+
 /**
  * Describes the x and y axis
  */
+#[derive(Clone, Copy)]
 pub enum Axis {
     XAxis = 0x01,
     YAxis = 0x00,
 }
 
 /// Registers for controller reading
+/// only valid when the game read its controllers, si::Controllers reads them itself
 pub const CONTROLLER1: *mut u32 = 0xBFC007C4 as *mut u32;
 pub const CONTROLLER2: *mut u32 = 0xBFC007CC as *mut u32;
+pub const CONTROLLER3: *mut u32 = 0xBFC007D4 as *mut u32;
+pub const CONTROLLER4: *mut u32 = 0xBFC007DC as *mut u32;
+pub const CONTROLLERS: [*mut u32; 4] = [CONTROLLER1, CONTROLLER2, CONTROLLER3, CONTROLLER4];
+
+/// how far the stick of a good controller goes in each direction
+pub const STICK_RANGE: i32 = 80;
+
+/// length of a stick vector
+pub fn magnitude(x: i8, y: i8) -> u8 {
+    let (x, y) = (x as i32, y as i32);
+    isqrt((x * x + y * y) as u32).min(u8::MAX as u32) as u8
+}
+
+/// angle of a stick vector in degrees, 0 is right and 90 is up
+pub fn stick_angle(x: i8, y: i8) -> u16 {
+    angle(x as i32, y as i32)
+}
+
+/**
+ * Corrects the stick of a controller
+ * The center is subtracted from the raw values and
+ * range is scaled to STICK_RANGE.
+ * Values inside deadzone are reported as 0,
+ * nav_deadzone is how far the stick has to go to move through menus.
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub struct StickCalibration {
+    pub center_x: i8,
+    pub center_y: i8,
+    /// the furthest the stick reaches from the center
+    pub range: u8,
+    pub deadzone: u8,
+    pub nav_deadzone: u8,
+}
+
+impl StickCalibration {
+    pub const fn new() -> Self {
+        Self {
+            center_x: 0,
+            center_y: 0,
+            range: STICK_RANGE as u8,
+            deadzone: 0,
+            nav_deadzone: 40,
+        }
+    }
+
+    fn scale(&self, value: i8, center: i8) -> i8 {
+        let range = (self.range as i32).max(1);
+        let value = (value as i32 - center as i32) * STICK_RANGE / range;
+        value.clamp(i8::MIN as i32, i8::MAX as i32) as i8
+    }
+
+    /// calibrated values of raw stick values
+    pub fn apply(&self, x: i8, y: i8) -> (i8, i8) {
+        let x = self.scale(x, self.center_x);
+        let y = self.scale(y, self.center_y);
+        if magnitude(x, y) <= self.deadzone {
+            (0, 0)
+        } else {
+            (x, y)
+        }
+    }
+}
+
+impl Default for StickCalibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /**
  * Handles an input
@@ -38,6 +151,7 @@ pub struct InputHandler {
     controller: *mut u32,
     current: u32,
     last: u32,
+    pub calibration: StickCalibration,
 }
 
 impl InputHandler {
@@ -46,6 +160,7 @@ impl InputHandler {
             controller,
             last: 0,
             current: 0,
+            calibration: StickCalibration::new(),
         }
     }
 
@@ -58,6 +173,12 @@ impl InputHandler {
         self.current = *self.controller;
     }
 
+    /// like update, with a status that was read some other way, e.g. by si::Controllers
+    pub fn update_from(&mut self, status: u32) {
+        self.last = self.current;
+        self.current = status;
+    }
+
     /**
      * Call before update to poll status of single button
      * if just is true return only if last is false
@@ -75,7 +196,167 @@ impl InputHandler {
         return ((readout >> (button as u32)) & 0x01) == 1;
     }
 
-    pub fn read_stick(&self, axis: Axis) -> u8 {
-        return ((self.current) >> (axis as u8 * 8)) as u8;
+    /// the raw controller status of this frame
+    pub fn current(&self) -> u32 {
+        self.current
+    }
+
+    pub fn read_stick(&self, axis: Axis) -> i8 {
+        return ((self.current) >> (axis as u8 * 8)) as i8;
+    }
+
+    /// uncalibrated x and y of a controller status
+    pub fn raw_stick(readout: u32) -> (i8, i8) {
+        if readout == 0xFFFFFFFF {
+            return (0, 0);
+        }
+        ((readout >> 8) as i8, readout as i8)
+    }
+
+    fn calibrated(&self, readout: u32) -> (i8, i8) {
+        if readout == 0xFFFFFFFF {
+            return (0, 0);
+        }
+        let (x, y) = Self::raw_stick(readout);
+        self.calibration.apply(x, y)
+    }
+
+    /// calibrated stick of this frame
+    pub fn stick(&self) -> (i8, i8) {
+        self.calibrated(self.current)
+    }
+
+    /// the d-pad direction the stick is pushed to past nav_deadzone
+    fn stick_direction(&self, readout: u32) -> Option<Button> {
+        let (x, y) = self.calibrated(readout);
+        let deadzone = self.calibration.nav_deadzone as i32;
+        let (x, y) = (x as i32, y as i32);
+
+        if x.abs().max(y.abs()) <= deadzone {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0 {
+                Button::DpadRight
+            } else {
+                Button::DpadLeft
+            })
+        } else {
+            Some(if y > 0 {
+                Button::DpadUp
+            } else {
+                Button::DpadDown
+            })
+        }
+    }
+
+    /**
+     * Like read_button for a d-pad button,
+     * but pushing the stick in that direction counts as well
+     */
+    pub fn read_nav(&self, button: Button, just: bool) -> bool {
+        let held = |readout| {
+            self.stick_direction(readout)
+                .is_some_and(|dir| dir as u32 == button as u32)
+        };
+        let stick = held(self.current) && (!just || !held(self.last));
+        self.read_button(button, just) || stick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a controller status with the buttons and raw stick
+    fn status(buttons: &[Button], x: i8, y: i8) -> u32 {
+        buttons
+            .iter()
+            .fold(0, |status, button| status | 1 << (*button as u32))
+            | (x as u8 as u32) << 8
+            | y as u8 as u32
+    }
+
+    fn handler(calibration: StickCalibration) -> InputHandler {
+        let mut input = InputHandler::new(core::ptr::null_mut());
+        input.calibration = calibration;
+        input
+    }
+
+    #[test]
+    fn magnitudes() {
+        assert_eq!(magnitude(0, 0), 0);
+        assert_eq!(magnitude(80, 0), 80);
+        assert_eq!(magnitude(0, -80), 80);
+        assert_eq!(magnitude(60, 80), 100);
+        assert_eq!(magnitude(-128, -128), 181);
+        assert_eq!(stick_angle(0, 80), 90);
+    }
+
+    #[test]
+    fn circular_deadzone() {
+        let cal = StickCalibration {
+            deadzone: 10,
+            ..StickCalibration::new()
+        };
+        assert!(cal.apply(10, 0) == (0, 0));
+        assert!(cal.apply(0, -10) == (0, 0));
+        assert!(cal.apply(11, 0) == (11, 0));
+        // inside the square but outside the circle
+        assert!(cal.apply(8, 8) == (8, 8));
+        assert!(cal.apply(-7, 7) == (0, 0));
+    }
+
+    #[test]
+    fn range_scaling() {
+        let cal = StickCalibration {
+            center_x: 4,
+            center_y: -2,
+            range: 40,
+            ..StickCalibration::new()
+        };
+        assert!(cal.apply(4, -2) == (0, 0));
+        assert!(cal.apply(44, -42) == (80, -80));
+        assert!(cal.apply(24, 8) == (40, 20));
+        // values past the range are clamped to i8
+        assert!(cal.apply(127, -128) == (127, -128));
+
+        let cal = StickCalibration::new();
+        assert!(cal.apply(-80, 80) == (-80, 80));
+    }
+
+    #[test]
+    fn missing_controller() {
+        let mut input = handler(StickCalibration::new());
+        input.update_from(0xFFFFFFFF);
+        assert_eq!(InputHandler::raw_stick(input.current()), (0, 0));
+        assert_eq!(input.stick(), (0, 0));
+        assert!(!input.read_button(Button::AInput, false));
+        assert!(!input.read_nav(Button::DpadUp, false));
+    }
+
+    #[test]
+    fn navigation() {
+        let mut input = handler(StickCalibration::new());
+        input.update_from(status(&[], 0, 0));
+
+        // past the menu deadzone counts once
+        input.update_from(status(&[], 0, 41));
+        assert!(input.read_nav(Button::DpadUp, true));
+        assert!(!input.read_nav(Button::DpadDown, true));
+        input.update_from(status(&[], 5, 60));
+        assert!(!input.read_nav(Button::DpadUp, true));
+        assert!(input.read_nav(Button::DpadUp, false));
+
+        // the larger axis wins
+        input.update_from(status(&[], -70, 50));
+        assert!(input.read_nav(Button::DpadLeft, true));
+        assert!(!input.read_nav(Button::DpadUp, false));
+
+        // inside the menu deadzone only the d-pad counts
+        input.update_from(status(&[Button::DpadRight], 0, -40));
+        assert!(input.read_nav(Button::DpadRight, true));
+        assert!(!input.read_nav(Button::DpadDown, false));
+        input.update_from(status(&[], 0, -41));
+        assert!(input.read_nav(Button::DpadDown, true));
     }
 }
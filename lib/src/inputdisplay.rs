@@ -0,0 +1,225 @@
+use crate::color::Color;
+use crate::input::{Button, InputHandler, CONTROLLERS, STICK_RANGE};
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::text::TextWriter;
+use core::marker::PhantomData;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum InputLayout {
+    /// a single line of at most 28 columns with the pressed buttons and the stick values
+    Compact,
+    /// buttons arranged like the controller and a stick box
+    Full,
+}
+
+impl InputLayout {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Compact => Self::Full,
+            Self::Full => Self::Compact,
+        }
+    }
+}
+
+/// button, column and row in the full layout
+const FULL_LAYOUT: [(Button, isize, isize); 14] = [
+    (Button::LInput, 0, 0),
+    (Button::ZInput, 5, 0),
+    (Button::RInput, 10, 0),
+    (Button::DpadUp, 1, 1),
+    (Button::StartInput, 5, 1),
+    (Button::CUInput, 9, 1),
+    (Button::DpadLeft, 0, 2),
+    (Button::DpadRight, 2, 2),
+    (Button::BInput, 5, 2),
+    (Button::CLInput, 8, 2),
+    (Button::CRInput, 10, 2),
+    (Button::DpadDown, 1, 3),
+    (Button::AInput, 6, 3),
+    (Button::CDInput, 9, 3),
+];
+
+/**
+ * Shows the buttons and the stick of a controller
+ */
+pub struct InputDisplay<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    toggle_timer_max: u16,
+    toggle_timer: u16,
+    pub active: bool,
+    pub layout: InputLayout,
+    port: usize,
+    input: InputHandler,
+    phantom: PhantomData<T>,
+}
+
+impl<T> InputDisplay<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, port: usize, layout: InputLayout) -> Self {
+        let port = port.min(CONTROLLERS.len() - 1);
+        Self {
+            x,
+            y,
+            toggle_timer_max: 10,
+            toggle_timer: 0,
+            active: false,
+            layout,
+            port,
+            input: InputHandler::new(CONTROLLERS[port]),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn port(&self) -> usize {
+        self.port
+    }
+
+    /// ports are 0 to 3
+    pub fn set_port(&mut self, port: usize) {
+        if port < CONTROLLERS.len() {
+            self.port = port;
+            self.input = InputHandler::new(CONTROLLERS[port]);
+        }
+    }
+
+    /// 0,0 when no controller is connected
+    pub fn stick(&self) -> (i8, i8) {
+        InputHandler::raw_stick(self.input.current())
+    }
+
+    fn pressed(&self, button: Button) -> bool {
+        self.input.read_button(button, false)
+    }
+
+    /// released buttons are drawn grey, or not at all without colors
+    fn draw_button(&self, ctxt: &mut dyn RenderContext, button: Button, x: isize, y: isize) {
+        if self.pressed(button) || ctxt.set_color(Color::new(0x60, 0x60, 0x60, 0xFF)) {
+            ctxt.puts(button.name(), x, y);
+        }
+    }
+
+    fn write_stick(&self, w: &mut TextWriter) {
+        let (x, y) = self.stick();
+        w.puts("x:");
+        w.signed(x as i64);
+        w.puts(" y:");
+        w.signed(y as i64);
+    }
+
+    /// every button keeps its place without a gap, the stick follows as x,y
+    fn draw_compact(&self, ctxt: &mut dyn RenderContext) {
+        let mut x = self.x;
+        for button in Button::ALL.iter() {
+            let len = button.name().len() as isize;
+            if self.pressed(*button) {
+                ctxt.puts(button.name(), x, self.y);
+            }
+            x += len * ctxt.char_width();
+        }
+
+        let (stick_x, stick_y) = self.stick();
+        let mut line = [0u8; 16];
+        let mut w = TextWriter::new(&mut line);
+        w.signed(stick_x as i64);
+        w.push(b',');
+        w.signed(stick_y as i64);
+        w.finish();
+        ctxt.putsu8(&line, x + ctxt.char_width(), self.y);
+    }
+
+    fn draw_full(&self, ctxt: &mut dyn RenderContext) {
+        let cw = ctxt.char_width();
+        let ch = ctxt.char_height();
+
+        for (button, col, row) in FULL_LAYOUT.iter() {
+            self.draw_button(ctxt, *button, self.x + col * cw, self.y + row * ch);
+        }
+
+        // stick box next to the buttons
+        let size = 4 * ch;
+        let box_x = self.x + 13 * cw;
+        let half = size / 2;
+        let range = STICK_RANGE as isize;
+        let (x, y) = self.stick();
+        let dot_x = box_x + half + (x as isize).clamp(-range, range) * half / range;
+        // up is positive on the stick
+        let dot_y = self.y + half - (y as isize).clamp(-range, range) * half / range;
+
+        if ctxt.fill_rect(
+            box_x,
+            self.y,
+            size,
+            size,
+            Color::new(0x30, 0x30, 0x30, 0xFF),
+        ) {
+            let line = Color::new(0x60, 0x60, 0x60, 0xFF);
+            ctxt.fill_rect(box_x + half, self.y, 1, size, line);
+            ctxt.fill_rect(box_x, self.y + half, size, 1, line);
+            ctxt.fill_rect(
+                dot_x - 2,
+                dot_y - 2,
+                4,
+                4,
+                Color::new(0xFF, 0xFF, 0xFF, 0xFF),
+            );
+        }
+
+        let mut line = [0u8; 16];
+        let mut w = TextWriter::new(&mut line);
+        self.write_stick(&mut w);
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y + 4 * ch);
+    }
+}
+
+impl<T> Drawable<T> for InputDisplay<T>
+where
+    T: Copy + Clone,
+{
+    fn update(&mut self, _data: T) {
+        if self.toggle_timer > 0 {
+            self.toggle_timer -= 1;
+        }
+
+        if self.active {
+            unsafe {
+                self.input.update();
+            }
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        match self.layout {
+            InputLayout::Compact => self.draw_compact(ctxt),
+            InputLayout::Full => self.draw_full(ctxt),
+        }
+    }
+}
+
+impl<T> Widget<T> for InputDisplay<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        if self.toggle_timer > 0 {
+            return;
+        }
+
+        self.toggle_timer = self.toggle_timer_max;
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -38,3 +38,20 @@ pub unsafe extern "C" fn enable_int(flags: usize) {
         options(noreturn)
     );
 }
+
+// $9 == $count
+pub type GetCountFn = unsafe extern "C" fn() -> u32;
+
+/// the count register increments at half the cpu clock
+#[naked]
+pub unsafe extern "C" fn get_count() -> u32 {
+    asm!(
+        r#"
+        mfc0 $v0, $9
+        nop
+        jr $ra
+        nop
+    "#,
+        options(noreturn)
+    );
+}
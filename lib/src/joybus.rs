@@ -0,0 +1,491 @@
+/**
+ * Joybus protocol and pif command blocks
+ * Nothing in here touches the hardware,
+ * si.rs sends the blocks to the pif.
+ */
+
+pub const PORTS: usize = 4;
+pub const PIF_RAM_LEN: usize = 64;
+
+/// the last byte of pif ram tells the pif what to do
+const CONTROL_BYTE: usize = PIF_RAM_LEN - 1;
+/// run the joybus commands in the block
+const CONTROL_RUN: u8 = 0x01;
+
+/// skips a channel
+const CHANNEL_SKIP: u8 = 0x00;
+/// no more commands after this
+const BLOCK_END: u8 = 0xFE;
+/// responses are filled with this before sending the block
+const RESPONSE_FILL: u8 = 0xFF;
+
+/// error flags the pif sets in the rx length
+const RX_NO_DEVICE: u8 = 0x80;
+const RX_OVERRUN: u8 = 0x40;
+
+pub const CMD_INFO: u8 = 0x00;
+pub const CMD_READ_BUTTONS: u8 = 0x01;
+pub const CMD_READ_ACCESSORY: u8 = 0x02;
+pub const CMD_WRITE_ACCESSORY: u8 = 0x03;
+pub const CMD_RESET: u8 = 0xFF;
+
+/// accessories are read and written 32 bytes at a time
+pub const ACCESSORY_BLOCK_LEN: usize = 32;
+
+/// bits of the status byte of a controller
+const STATUS_ACCESSORY: u8 = 0x01;
+const STATUS_ADDRESS_CRC: u8 = 0x04;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum JoybusError {
+    /// nothing answered on the port
+    NoDevice,
+    /// the device did not send what was expected
+    Overrun,
+    /// the commands do not fit into pif ram
+    BlockFull,
+    /// the port has no command in the block or was added twice
+    BadPort,
+    /// the accessory data did not match its crc
+    Crc,
+    /// the controller reported no accessory for an accessory command
+    NoAccessory,
+    /// the game is using the si
+    Busy,
+}
+
+#[derive(Copy, Clone)]
+pub enum Command {
+    /// device type and status
+    Info,
+    /// like Info, also resets the device
+    Reset,
+    ReadButtons,
+    /// address without crc, 32 byte aligned
+    ReadAccessory(u16),
+    WriteAccessory(u16, [u8; ACCESSORY_BLOCK_LEN]),
+}
+
+impl Command {
+    pub fn tx_len(&self) -> usize {
+        match self {
+            Self::Info | Self::Reset | Self::ReadButtons => 1,
+            Self::ReadAccessory(_) => 3,
+            Self::WriteAccessory(_, _) => 3 + ACCESSORY_BLOCK_LEN,
+        }
+    }
+
+    pub fn rx_len(&self) -> usize {
+        match self {
+            Self::Info | Self::Reset => 3,
+            Self::ReadButtons => 4,
+            Self::ReadAccessory(_) => ACCESSORY_BLOCK_LEN + 1,
+            Self::WriteAccessory(_, _) => 1,
+        }
+    }
+
+    /// writes the command byte and its arguments, buffer has to be tx_len long
+    fn encode(&self, buffer: &mut [u8]) {
+        match self {
+            Self::Info => buffer[0] = CMD_INFO,
+            Self::Reset => buffer[0] = CMD_RESET,
+            Self::ReadButtons => buffer[0] = CMD_READ_BUTTONS,
+            Self::ReadAccessory(addr) => {
+                buffer[0] = CMD_READ_ACCESSORY;
+                buffer[1..3].copy_from_slice(&address_crc(*addr).to_be_bytes());
+            }
+            Self::WriteAccessory(addr, data) => {
+                buffer[0] = CMD_WRITE_ACCESSORY;
+                buffer[1..3].copy_from_slice(&address_crc(*addr).to_be_bytes());
+                buffer[3..].copy_from_slice(data);
+            }
+        }
+    }
+}
+
+/**
+ * Adds the 5 bit crc to an accessory address
+ * the lower 5 bits of the address are dropped
+ */
+pub fn address_crc(addr: u16) -> u16 {
+    const XOR_TABLE: [u16; 16] = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0x1F, 0x0B, 0x16, 0x19, 0x07, 0x0E, 0x1C, 0x0D, 0x1A,
+        0x01,
+    ];
+
+    let addr = addr & !0x1F;
+    let crc = (5..16)
+        .filter(|bit| (addr >> bit) & 1 != 0)
+        .fold(0, |crc, bit| crc ^ XOR_TABLE[bit]);
+    addr | (crc & 0x1F)
+}
+
+/// crc the controller sends after accessory data
+pub fn data_crc(data: &[u8; ACCESSORY_BLOCK_LEN]) -> u8 {
+    let mut crc = 0u8;
+    // one extra byte of zeros flushes the crc
+    for byte in data.iter().chain([0u8].iter()) {
+        for bit in (0..8).rev() {
+            let xor = if crc & 0x80 != 0 { 0x85 } else { 0 };
+            crc <<= 1;
+            if byte & (1 << bit) != 0 {
+                crc |= 1;
+            }
+            crc ^= xor;
+        }
+    }
+    crc
+}
+
+/// checks the crc of a ReadAccessory or WriteAccessory response
+pub fn check_data_crc(data: &[u8; ACCESSORY_BLOCK_LEN], crc: u8) -> Result<(), JoybusError> {
+    let expected = data_crc(data);
+    if crc == expected {
+        Ok(())
+    } else if crc == !expected {
+        // the controller inverts the crc when no accessory is inserted
+        Err(JoybusError::NoAccessory)
+    } else {
+        Err(JoybusError::Crc)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DeviceType {
+    Controller,
+    Mouse,
+    VoiceRecognition,
+    Keyboard,
+    Eeprom4k,
+    Eeprom16k,
+    Unknown(u16),
+}
+
+impl DeviceType {
+    pub fn from_id(id: u16) -> Self {
+        match id {
+            0x0500 => Self::Controller,
+            0x0200 => Self::Mouse,
+            0x0001 => Self::VoiceRecognition,
+            0x0002 => Self::Keyboard,
+            0x0080 => Self::Eeprom4k,
+            0x00C0 => Self::Eeprom16k,
+            _ => Self::Unknown(id),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Controller => "controller",
+            Self::Mouse => "mouse",
+            Self::VoiceRecognition => "vru",
+            Self::Keyboard => "keyboard",
+            Self::Eeprom4k => "eeprom 4k",
+            Self::Eeprom16k => "eeprom 16k",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// response to Info and Reset
+#[derive(Copy, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub device: DeviceType,
+    pub status: u8,
+}
+
+impl DeviceInfo {
+    pub fn decode(response: &[u8]) -> Self {
+        Self {
+            device: DeviceType::from_id(u16::from_be_bytes([response[0], response[1]])),
+            status: response[2],
+        }
+    }
+
+    /// only means something for controllers
+    pub fn has_accessory(&self) -> bool {
+        self.status & STATUS_ACCESSORY != 0
+    }
+
+    /// the last accessory address was sent with a bad crc
+    pub fn address_crc_error(&self) -> bool {
+        self.status & STATUS_ADDRESS_CRC != 0
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Accessory {
+    None,
+    ControllerPak,
+    RumblePak,
+    TransferPak,
+}
+
+impl Accessory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::ControllerPak => "controller pak",
+            Self::RumblePak => "rumble pak",
+            Self::TransferPak => "transfer pak",
+        }
+    }
+}
+
+/// where the response of a port is in the block
+#[derive(Copy, Clone)]
+struct Slot {
+    /// the pif writes the error flags into the rx length
+    len_pos: usize,
+    rx_pos: usize,
+    rx_len: usize,
+}
+
+/**
+ * The 64 bytes that are sent to pif ram
+ * Every entry in the block belongs to the next channel,
+ * so each port can get one command and ports have to be added in order.
+ * data is aligned for si dma.
+ */
+#[repr(C, align(16))]
+pub struct PifBlock {
+    pub data: [u8; PIF_RAM_LEN],
+    pos: usize,
+    next_port: usize,
+    slots: [Option<Slot>; PORTS],
+}
+
+impl PifBlock {
+    pub fn new() -> Self {
+        Self {
+            data: [0; PIF_RAM_LEN],
+            pos: 0,
+            next_port: 0,
+            slots: [None; PORTS],
+        }
+    }
+
+    /// a block with the same command for every port
+    pub fn all(command: Command) -> Result<Self, JoybusError> {
+        let mut block = Self::new();
+        for port in 0..PORTS {
+            block.add(port, command)?;
+        }
+        block.finish();
+        Ok(block)
+    }
+
+    /// a block with a single command
+    pub fn single(port: usize, command: Command) -> Result<Self, JoybusError> {
+        let mut block = Self::new();
+        block.add(port, command)?;
+        block.finish();
+        Ok(block)
+    }
+
+    pub fn add(&mut self, port: usize, command: Command) -> Result<(), JoybusError> {
+        if port >= PORTS || port < self.next_port {
+            return Err(JoybusError::BadPort);
+        }
+
+        let skips = port - self.next_port;
+        let (tx_len, rx_len) = (command.tx_len(), command.rx_len());
+        // room for the end marker is kept
+        if self.pos + skips + 2 + tx_len + rx_len + 1 > CONTROL_BYTE {
+            return Err(JoybusError::BlockFull);
+        }
+
+        for _ in 0..skips {
+            self.data[self.pos] = CHANNEL_SKIP;
+            self.pos += 1;
+        }
+
+        let len_pos = self.pos + 1;
+        self.data[self.pos] = tx_len as u8;
+        self.data[len_pos] = rx_len as u8;
+        let tx_pos = self.pos + 2;
+        command.encode(&mut self.data[tx_pos..tx_pos + tx_len]);
+        let rx_pos = tx_pos + tx_len;
+        self.data[rx_pos..rx_pos + rx_len].fill(RESPONSE_FILL);
+
+        self.slots[port] = Some(Slot {
+            len_pos,
+            rx_pos,
+            rx_len,
+        });
+        self.pos = rx_pos + rx_len;
+        self.next_port = port + 1;
+        Ok(())
+    }
+
+    /// ends the command list and tells the pif to run it
+    pub fn finish(&mut self) {
+        self.data[self.pos] = BLOCK_END;
+        self.data[CONTROL_BYTE] = CONTROL_RUN;
+    }
+
+    /// the response of a port after the block came back from the pif
+    pub fn response(&self, port: usize) -> Result<&[u8], JoybusError> {
+        let slot = match self.slots.get(port) {
+            Some(Some(slot)) => slot,
+            _ => return Err(JoybusError::BadPort),
+        };
+
+        let rx = self.data[slot.len_pos];
+        if rx & RX_NO_DEVICE != 0 {
+            Err(JoybusError::NoDevice)
+        } else if rx & RX_OVERRUN != 0 {
+            Err(JoybusError::Overrun)
+        } else {
+            Ok(&self.data[slot.rx_pos..slot.rx_pos + slot.rx_len])
+        }
+    }
+
+    pub fn info(&self, port: usize) -> Result<DeviceInfo, JoybusError> {
+        Ok(DeviceInfo::decode(self.response(port)?))
+    }
+
+    /// buttons and stick in the same layout as the controller status in pif ram
+    pub fn buttons(&self, port: usize) -> Result<u32, JoybusError> {
+        let r = self.response(port)?;
+        Ok(u32::from_be_bytes([r[0], r[1], r[2], r[3]]))
+    }
+
+    /// accessory data of a ReadAccessory command
+    pub fn accessory_data(&self, port: usize) -> Result<[u8; ACCESSORY_BLOCK_LEN], JoybusError> {
+        let r = self.response(port)?;
+        let mut data = [0u8; ACCESSORY_BLOCK_LEN];
+        data.copy_from_slice(&r[..ACCESSORY_BLOCK_LEN]);
+        check_data_crc(&data, r[ACCESSORY_BLOCK_LEN])?;
+        Ok(data)
+    }
+}
+
+impl Default for PifBlock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_crcs() {
+        assert_eq!(address_crc(0x0000), 0x0000);
+        assert_eq!(address_crc(0x8000), 0x8001);
+        assert_eq!(address_crc(0xC000), 0xC01B);
+        // the low bits are replaced by the crc
+        assert_eq!(address_crc(0x801F), 0x8001);
+    }
+
+    #[test]
+    fn data_crcs() {
+        assert_eq!(data_crc(&[0x00; ACCESSORY_BLOCK_LEN]), 0x00);
+        assert_eq!(data_crc(&[0x01; ACCESSORY_BLOCK_LEN]), 0xEB);
+        assert_eq!(data_crc(&[0x80; ACCESSORY_BLOCK_LEN]), 0xB8);
+
+        let data = [0x80; ACCESSORY_BLOCK_LEN];
+        assert!(check_data_crc(&data, 0xB8).is_ok());
+        assert!(check_data_crc(&data, !0xB8).err() == Some(JoybusError::NoAccessory));
+        assert!(check_data_crc(&data, 0x00).err() == Some(JoybusError::Crc));
+    }
+
+    #[test]
+    fn skips_channels() {
+        let block = PifBlock::single(2, Command::ReadButtons).ok().unwrap();
+        assert_eq!(
+            block.data[..10],
+            [
+                0x00,
+                0x00,
+                1,
+                4,
+                CMD_READ_BUTTONS,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFE
+            ]
+        );
+        assert_eq!(block.data[CONTROL_BYTE], CONTROL_RUN);
+    }
+
+    #[test]
+    fn every_port() {
+        let block = PifBlock::all(Command::Info).ok().unwrap();
+        for port in 0..PORTS {
+            let entry = &block.data[port * 6..port * 6 + 6];
+            assert_eq!(entry, [1, 3, CMD_INFO, 0xFF, 0xFF, 0xFF]);
+        }
+        assert_eq!(block.data[PORTS * 6], BLOCK_END);
+        assert_eq!(block.data[CONTROL_BYTE], CONTROL_RUN);
+    }
+
+    #[test]
+    fn accessory_commands() {
+        let block = PifBlock::single(0, Command::ReadAccessory(0x8000))
+            .ok()
+            .unwrap();
+        assert_eq!(block.data[..5], [3, 33, CMD_READ_ACCESSORY, 0x80, 0x01]);
+        assert_eq!(block.data[5 + ACCESSORY_BLOCK_LEN + 1], BLOCK_END);
+
+        let block = PifBlock::single(0, Command::WriteAccessory(0xC000, [1; 32]))
+            .ok()
+            .unwrap();
+        assert_eq!(block.data[..5], [35, 1, CMD_WRITE_ACCESSORY, 0xC0, 0x1B]);
+        assert_eq!(block.data[5..37], [1; 32]);
+        assert_eq!(block.data[37..39], [0xFF, BLOCK_END]);
+    }
+
+    #[test]
+    fn bad_ports_and_full_blocks() {
+        let mut block = PifBlock::new();
+        assert!(block.add(PORTS, Command::Info).err() == Some(JoybusError::BadPort));
+        assert!(block.add(1, Command::Info).is_ok());
+        assert!(block.add(1, Command::Info).err() == Some(JoybusError::BadPort));
+        assert!(block.add(0, Command::Info).err() == Some(JoybusError::BadPort));
+        assert!(block.response(0).err() == Some(JoybusError::BadPort));
+
+        let write = Command::WriteAccessory(0, [0; ACCESSORY_BLOCK_LEN]);
+        let mut block = PifBlock::new();
+        assert!(block.add(0, write).is_ok());
+        assert!(block.add(1, write).err() == Some(JoybusError::BlockFull));
+    }
+
+    #[test]
+    fn responses() {
+        let mut block = PifBlock::all(Command::ReadButtons).ok().unwrap();
+        // what the pif sends back: a controller, nothing, an overrun, a controller
+        block.data[3..7].copy_from_slice(&[0x80, 0x00, 0x12, 0x34]);
+        block.data[8] |= RX_NO_DEVICE;
+        block.data[15] |= RX_OVERRUN;
+        block.data[24..28].copy_from_slice(&[0x00, 0x20, 0xB0, 0x50]);
+
+        assert!(block.buttons(0).ok() == Some(0x80001234));
+        assert!(block.buttons(1).err() == Some(JoybusError::NoDevice));
+        assert!(block.buttons(2).err() == Some(JoybusError::Overrun));
+        assert!(block.buttons(3).ok() == Some(0x0020B050));
+    }
+
+    #[test]
+    fn info_and_accessory_data() {
+        let mut block = PifBlock::single(0, Command::Info).ok().unwrap();
+        block.data[3..6].copy_from_slice(&[0x05, 0x00, 0x01]);
+        let info = block.info(0).ok().unwrap();
+        assert!(info.device == DeviceType::Controller);
+        assert!(info.has_accessory());
+        assert!(!info.address_crc_error());
+
+        let mut block = PifBlock::single(0, Command::ReadAccessory(0x8000))
+            .ok()
+            .unwrap();
+        block.data[5..37].fill(0x80);
+        block.data[37] = 0xB8;
+        assert!(block.accessory_data(0).ok() == Some([0x80; ACCESSORY_BLOCK_LEN]));
+        block.data[37] = !0xB8;
+        assert!(block.accessory_data(0).err() == Some(JoybusError::NoAccessory));
+    }
+}
@@ -6,9 +6,15 @@ use super::render::{Drawable, RenderContext, Widget};
 
 pub static HEX_ROW1: [u8; 5] = [b'0', b'1', b'2', b'3', b'4'];
 pub static HEX_ROW2: [u8; 5] = [b'5', b'6', b'7', b'8', b'9'];
-pub static HEX_ROW3: [u8; 5] = [b'A', b'C', b'D', b'E', b'F'];
+pub static HEX_ROW3: [u8; 6] = [b'A', b'B', b'C', b'D', b'E', b'F'];
 pub static HEX: [&[u8]; 3] = [&HEX_ROW1, &HEX_ROW2, &HEX_ROW3];
 
+pub static UPPER: [&[u8]; 3] = [b"ABCDEFGHI", b"JKLMNOPQR", b"STUVWXYZ "];
+pub static LOWER: [&[u8]; 3] = [b"abcdefghi", b"jklmnopqr", b"stuvwxyz "];
+pub static DIGITS: [&[u8]; 2] = [b"01234", b"56789"];
+pub static SYMBOLS: [&[u8]; 4] = [b"!\"#$%&'()", b"*+,-./:;<", b"=>?@[\\]^_", b"`{|}~ "];
+pub static POINTER: [&[u8]; 3] = [b"01234567", b"89ABCDEF", b"[]+-x"];
+
 /**
  * Represents a simple keyboard
  * that can be rendered to any render context
@@ -49,13 +55,13 @@ where
     }
 
     pub fn down(&mut self) {
-        if self.cursor_y < self.grid.len() {
+        if self.cursor_y + 1 < self.grid.len() {
             self.cursor_y += 1;
         } else {
             self.cursor_y = 0;
         }
 
-        self.cursor_x = usize::min(self.cursor_x, self.grid[self.cursor_y].len());
+        self.cursor_x = usize::min(self.cursor_x, self.grid[self.cursor_y].len() - 1);
     }
 
     pub fn up(&mut self) {
@@ -64,7 +70,7 @@ where
         } else {
             self.cursor_y = self.grid.len() - 1;
         }
-        self.cursor_x = usize::min(self.cursor_x, self.grid[self.cursor_y].len());
+        self.cursor_x = usize::min(self.cursor_x, self.grid[self.cursor_y].len() - 1);
     }
 
     pub fn left(&mut self) {
@@ -76,13 +82,28 @@ where
     }
 
     pub fn right(&mut self) {
-        if self.cursor_x < self.grid[self.cursor_y].len() {
+        if self.cursor_x + 1 < self.grid[self.cursor_y].len() {
             self.cursor_x += 1;
         } else {
             self.cursor_x = 0;
         }
     }
 
+    /// the key under the cursor
+    pub fn current(&self) -> u8 {
+        self.grid[self.cursor_y][self.cursor_x]
+    }
+
+    /**
+     * Switches to a different grid
+     * the cursor is clamped to the new grid
+     */
+    pub fn set_grid(&mut self, grid: &'a [&'a [u8]]) {
+        self.grid = grid;
+        self.cursor_y = usize::min(self.cursor_y, grid.len() - 1);
+        self.cursor_x = usize::min(self.cursor_x, grid[self.cursor_y].len() - 1);
+    }
+
     pub fn select(&mut self, buffer: &mut [u8]) {
         if self.position < buffer.len() {
             buffer[self.position] = self.grid[self.cursor_y][self.cursor_x];
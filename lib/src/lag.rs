@@ -0,0 +1,308 @@
+use crate::clock::{Clock, COUNT_HZ};
+use crate::color::Color;
+use crate::memmap::MemoryMap;
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::text::TextWriter;
+use crate::watch::WatchType;
+use core::marker::PhantomData;
+
+/// how game frames are counted
+#[derive(Copy, Clone)]
+pub enum FrameSource {
+    /// every update call is one game frame
+    Hook,
+    /// a counter the game increments once per logic frame
+    Counter(WatchType),
+}
+
+/// a field in millifields
+const FIELD: i64 = 1000;
+
+/**
+ * Compares vertical interrupts to game frames
+ * A game that runs at target fields per frame
+ * lags whenever more fields pass than its frames took.
+ * Lag is counted in missed game frames, a frame that is
+ * at least half a field late counts as one.
+ */
+#[derive(Copy, Clone)]
+pub struct LagStats {
+    /// vi fields per game frame at full speed, e.g. 2 for 30fps games
+    pub target: u32,
+    pub millifields: u64,
+    pub frames: u64,
+    pub lag: u64,
+    pub segment_lag: u64,
+    /// time that is not accounted for by frames yet, in 1/1000 fields
+    balance: i64,
+}
+
+impl LagStats {
+    pub fn new(target: u32) -> Self {
+        Self {
+            target: target.max(1),
+            millifields: 0,
+            frames: 0,
+            lag: 0,
+            segment_lag: 0,
+            balance: 0,
+        }
+    }
+
+    pub fn fields(&self) -> u64 {
+        self.millifields / FIELD as u64
+    }
+
+    /// adds the vertical interrupts and frames since the last step
+    pub fn step(&mut self, fields: u64, frames: u64) {
+        let target = self.target as i64 * FIELD;
+        let millifields = fields * FIELD as u64;
+        self.millifields += millifields;
+        self.frames += frames;
+
+        self.balance += millifields as i64 - frames as i64 * target;
+
+        let mut lagged = false;
+        while self.balance >= FIELD / 2 {
+            self.balance -= target;
+            self.lag += 1;
+            self.segment_lag += 1;
+            lagged = true;
+        }
+
+        // a frame that came a field early only makes up for the next one,
+        // so the hook running just before a vi does not look like lag
+        let credit = if lagged { 0 } else { -FIELD };
+        self.balance = self.balance.max(credit);
+    }
+
+    pub fn new_segment(&mut self) {
+        self.segment_lag = 0;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.target);
+    }
+}
+
+/**
+ * Shows the game's frame rate and lag frames
+ * vi has to be called from the game's vertical interrupt hook
+ * (e.g. its vi retrace handler), update from its frame hook
+ * before frame advance holds the game.
+ * Only the fps uses the count register.
+ */
+pub struct LagCounter<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    pub source: FrameSource,
+    pub stats: LagStats,
+    pub memory: MemoryMap,
+    clock: Clock,
+    /// vertical interrupts seen by the vi hook, wraps
+    vis: u32,
+    /// vertical interrupts already passed to stats
+    counted: u32,
+    last_counter: Option<u64>,
+    /// frames and count ticks since the fps was last calculated
+    window_frames: u64,
+    window_ticks: u64,
+    /// frames per second * 100
+    fps: u64,
+    phantom: PhantomData<T>,
+}
+
+impl<T> LagCounter<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, source: FrameSource, target: u32) -> Self {
+        Self {
+            x,
+            y,
+            active: false,
+            source,
+            stats: LagStats::new(target),
+            memory: MemoryMap::detect(),
+            clock: Clock::new(),
+            vis: 0,
+            counted: 0,
+            last_counter: None,
+            window_frames: 0,
+            window_ticks: 0,
+            fps: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// frames per second * 100
+    pub fn fps(&self) -> u64 {
+        self.fps
+    }
+
+    pub fn new_segment(&mut self) {
+        self.stats.new_segment();
+    }
+
+    pub fn reset(&mut self) {
+        self.stats.reset();
+        self.counted = self.vis;
+        self.window_frames = 0;
+        self.window_ticks = 0;
+    }
+
+    /// skips the time the game was held, e.g. from FrameAdvance::with_resume
+    pub fn resume(&mut self) {
+        self.counted = self.vis;
+        self.clock.resume();
+    }
+
+    /// call once per vertical interrupt from the game's vi hook
+    pub fn vi(&mut self) {
+        self.vis = self.vis.wrapping_add(1);
+    }
+
+    /// game frames since the last update
+    fn frames(&mut self) -> u64 {
+        let watch_type = match self.source {
+            FrameSource::Hook => return 1,
+            FrameSource::Counter(watch_type) => watch_type,
+        };
+
+        let current = watch_type.read(&self.memory).ok();
+        let previous = self.last_counter;
+        self.last_counter = current;
+
+        match (previous, current) {
+            (Some(previous), Some(current)) => {
+                let bits = watch_type.size() as u32 * 8;
+                let mask = u64::MAX >> (64 - bits);
+                current.wrapping_sub(previous) & mask
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl<T> Drawable<T> for LagCounter<T>
+where
+    T: Copy + Clone,
+{
+    /// call once from the game's frame hook
+    fn update(&mut self, _data: T) {
+        let vis = self.vis;
+        let fields = vis.wrapping_sub(self.counted) as u64;
+        self.counted = vis;
+
+        let frames = self.frames();
+        self.stats.step(fields, frames);
+
+        // the fps is averaged over one second
+        let start = self.clock.ticks();
+        self.clock.update();
+        self.window_frames += frames;
+        self.window_ticks += self.clock.ticks() - start;
+        if self.window_ticks >= COUNT_HZ {
+            self.fps = self.window_frames * COUNT_HZ * 100 / self.window_ticks;
+            self.window_frames = 0;
+            self.window_ticks = 0;
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        let mut line = [0u8; 40];
+        let mut w = TextWriter::new(&mut line);
+        w.dec(self.fps / 100);
+        w.push(b'.');
+        w.push(b'0' + (self.fps / 10 % 10) as u8);
+        w.puts(" fps lag ");
+        w.dec(self.stats.lag);
+        w.puts(" (");
+        w.dec(self.stats.segment_lag);
+        w.push(b')');
+        w.finish();
+
+        if self.stats.segment_lag > 0 {
+            ctxt.set_color(Color::new(0xFF, 0x80, 0x00, 0xFF));
+        }
+        ctxt.putsu8(&line, self.x, self.y);
+    }
+}
+
+impl<T> Widget<T> for LagCounter<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(target: u32, steps: &[(u64, u64)]) -> LagStats {
+        let mut stats = LagStats::new(target);
+        for (fields, frames) in steps {
+            stats.step(*fields, *frames);
+        }
+        stats
+    }
+
+    #[test]
+    fn full_speed() {
+        let stats = run(1, &[(1, 1); 100]);
+        assert_eq!((stats.lag, stats.fields(), stats.frames), (0, 100, 100));
+        assert_eq!(run(2, &[(2, 1); 100]).lag, 0);
+        // a counter that went up twice between two hooks
+        assert_eq!(run(2, &[(4, 2), (2, 1)]).lag, 0);
+    }
+
+    #[test]
+    fn late_frames_at_60fps() {
+        assert_eq!(run(1, &[(1, 1), (2, 1), (1, 1)]).lag, 1);
+        assert_eq!(run(1, &[(3, 1)]).lag, 2);
+        assert_eq!(run(1, &[(2, 1), (2, 1), (2, 1)]).lag, 3);
+    }
+
+    #[test]
+    fn late_frames_at_30fps() {
+        // a field late is half a frame late
+        assert_eq!(run(2, &[(3, 1)]).lag, 1);
+        assert_eq!(run(2, &[(3, 1), (2, 1), (3, 1)]).lag, 2);
+        assert_eq!(run(2, &[(4, 1)]).lag, 1);
+        assert_eq!(run(2, &[(6, 1)]).lag, 2);
+        assert_eq!(run(2, &[(5, 1)]).lag, 2);
+    }
+
+    #[test]
+    fn hook_jitter_is_not_lag() {
+        assert_eq!(run(1, &[(0, 1), (2, 1), (1, 1)]).lag, 0);
+        assert_eq!(run(2, &[(1, 1), (3, 1)]).lag, 0);
+        // early frames do not add up
+        assert_eq!(run(1, &[(0, 1), (0, 1), (2, 1), (2, 1)]).lag, 1);
+    }
+
+    #[test]
+    fn segments() {
+        let mut stats = run(1, &[(2, 1)]);
+        stats.new_segment();
+        stats.step(3, 1);
+        assert_eq!((stats.lag, stats.segment_lag), (3, 2));
+        stats.reset();
+        assert_eq!((stats.lag, stats.segment_lag, stats.target), (0, 0, 1));
+    }
+}
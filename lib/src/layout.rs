@@ -0,0 +1,489 @@
+use crate::color::Color;
+use crate::memmap::{MemoryError, MemoryMap};
+use crate::menu::EntryTypes;
+use crate::numeric::{Base, NumberInput, Width};
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::text::TextWriter;
+use crate::textinput::InputResult;
+use crate::watch::{WatchAddr, WatchFormat, WatchType};
+
+/// longest line a single field can draw
+const LINE_LEN: usize = 48;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    /// a 32 bit address, shown in hex
+    Ptr,
+}
+
+impl FieldType {
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 | Self::S8 => 1,
+            Self::U16 | Self::S16 => 2,
+            Self::U32 | Self::S32 | Self::F32 | Self::Ptr => 4,
+            Self::U64 | Self::S64 => 8,
+        }
+    }
+
+    /// the watch type that reads this field at addr
+    pub fn watch_type(&self, addr: usize) -> WatchType {
+        match self {
+            Self::U8 => WatchType::UInt8(addr as *const u8),
+            Self::U16 => WatchType::UInt16(addr as *const u16),
+            Self::U32 | Self::Ptr => WatchType::UInt32(addr as *const u32),
+            Self::U64 => WatchType::UInt64(addr as *const u64),
+            Self::S8 => WatchType::Int8(addr as *const i8),
+            Self::S16 => WatchType::Int16(addr as *const i16),
+            Self::S32 => WatchType::Int32(addr as *const i32),
+            Self::S64 => WatchType::Int64(addr as *const i64),
+            Self::F32 => WatchType::Float32(addr as *const f32),
+        }
+    }
+
+    pub fn format(&self) -> WatchFormat {
+        match self {
+            Self::Ptr => WatchFormat::Hex,
+            _ => self.watch_type(0).default_format(),
+        }
+    }
+
+    /**
+     * The number input that edits this field
+     * floats are edited as raw bits and 64 bit values can not be edited
+     */
+    fn input<T: Copy + Clone>(&self, x: isize, y: isize) -> Option<NumberInput<T>> {
+        let (base, width, signed) = match self {
+            Self::U8 => (Base::Hex, Width::U8, false),
+            Self::U16 => (Base::Hex, Width::U16, false),
+            Self::U32 | Self::Ptr | Self::F32 => (Base::Hex, Width::U32, false),
+            Self::S8 => (Base::Dec, Width::U8, true),
+            Self::S16 => (Base::Dec, Width::U16, true),
+            Self::S32 => (Base::Dec, Width::U32, true),
+            Self::U64 | Self::S64 => return None,
+        };
+        Some(NumberInput::new(x, y, base, width, signed))
+    }
+}
+
+/// names for the values or bits of a field
+#[derive(Copy, Clone)]
+pub enum Labels {
+    None,
+    /// value and name
+    Enum(&'static [(u64, &'static str)]),
+    /// bit index and name
+    Flags(&'static [(u8, &'static str)]),
+}
+
+/**
+ * A single field of a struct
+ * arrays are count elements of the same type
+ * directly after each other
+ */
+#[derive(Copy, Clone)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub field_type: FieldType,
+    pub count: usize,
+    pub labels: Labels,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, offset: usize, field_type: FieldType) -> Self {
+        Self {
+            name,
+            offset,
+            field_type,
+            count: 1,
+            labels: Labels::None,
+        }
+    }
+
+    pub const fn array(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    pub const fn with_enum(mut self, labels: &'static [(u64, &'static str)]) -> Self {
+        self.labels = Labels::Enum(labels);
+        self
+    }
+
+    pub const fn with_flags(mut self, labels: &'static [(u8, &'static str)]) -> Self {
+        self.labels = Labels::Flags(labels);
+        self
+    }
+
+    /// offset of an array element from the start of the struct
+    pub fn element_offset(&self, index: usize) -> usize {
+        self.offset + index * self.field_type.size()
+    }
+
+    /// writes the enum name or the names of all set flags
+    fn write_labels(&self, raw: u64, w: &mut TextWriter) {
+        match self.labels {
+            Labels::None => {}
+            Labels::Enum(labels) => {
+                if let Some((_, name)) = labels.iter().find(|(value, _)| *value == raw) {
+                    w.push(b' ');
+                    w.puts(name);
+                }
+            }
+            Labels::Flags(labels) => {
+                let mut first = true;
+                for (bit, name) in labels.iter() {
+                    if *bit < 64 && raw & (1 << bit) != 0 {
+                        w.push(if first { b' ' } else { b'|' });
+                        w.puts(name);
+                        first = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Describes a game struct
+ * e.g.
+ * static PLAYER: StructLayout = StructLayout::new("Player", 0x20, &[
+ *     Field::new("state", 0x00, FieldType::U32).with_enum(&[(0, "idle"), (1, "walk")]),
+ *     Field::new("flags", 0x04, FieldType::U16).with_flags(&[(0, "ground"), (1, "water")]),
+ *     Field::new("pos", 0x08, FieldType::F32).array(3),
+ * ]);
+ */
+pub struct StructLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub fields: &'static [Field],
+}
+
+impl StructLayout {
+    pub const fn new(name: &'static str, size: usize, fields: &'static [Field]) -> Self {
+        Self { name, size, fields }
+    }
+
+    /// every array element takes up its own row
+    pub fn rows(&self) -> usize {
+        self.fields.iter().map(|f| f.count).sum()
+    }
+
+    /// the field and array index of a row
+    pub fn row(&self, row: usize) -> Option<(&Field, usize)> {
+        let mut first = 0;
+        for field in self.fields {
+            if row < first + field.count {
+                return Some((field, row - first));
+            }
+            first += field.count;
+        }
+        None
+    }
+}
+
+/**
+ * Shows a struct instance at an address
+ * as a list of fields with their current values.
+ * Selecting a field edits it in place.
+ */
+pub struct StructView<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    rows: usize,
+    cursor: usize,
+    toggle_timer_max: u16,
+    toggle_timer: u16,
+    close_action: EntryTypes<T>,
+    back_action: EntryTypes<T>,
+    layout: &'static StructLayout,
+    pub base: usize,
+    pub memory: MemoryMap,
+    number: Option<NumberInput<T>>,
+    /// address the number input writes to
+    edit_addr: usize,
+    pub last_error: Option<MemoryError>,
+}
+
+impl<T> StructView<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(
+        x: isize,
+        y: isize,
+        layout: &'static StructLayout,
+        close_action: EntryTypes<T>,
+        back_action: EntryTypes<T>,
+    ) -> Self {
+        Self {
+            x,
+            y,
+            active: false,
+            rows: 10,
+            cursor: 0,
+            toggle_timer_max: 10,
+            toggle_timer: 0,
+            close_action,
+            back_action,
+            layout,
+            base: 0x80000000,
+            memory: MemoryMap::detect(),
+            number: None,
+            edit_addr: 0,
+            last_error: None,
+        }
+    }
+
+    /// shows a different struct or instance
+    pub fn view(&mut self, layout: &'static StructLayout, base: usize) {
+        self.layout = layout;
+        self.base = base;
+        self.cursor = 0;
+        self.number = None;
+    }
+
+    fn editing(&self) -> bool {
+        self.number.is_some_and(|n| n.active())
+    }
+
+    pub fn left(&mut self) {
+        if let Some(number) = &mut self.number {
+            number.left();
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let Some(number) = &mut self.number {
+            number.right();
+        }
+    }
+
+    pub fn up(&mut self) {
+        if self.editing() {
+            if let Some(number) = &mut self.number {
+                number.up();
+            }
+        } else if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.editing() {
+            if let Some(number) = &mut self.number {
+                number.down();
+            }
+        } else if self.cursor + 1 < self.layout.rows() {
+            self.cursor += 1;
+        }
+    }
+
+    /// address of the field under the cursor
+    pub fn selected(&self) -> Option<(&Field, usize)> {
+        let (field, index) = self.layout.row(self.cursor)?;
+        Some((field, self.base + field.element_offset(index)))
+    }
+
+    /**
+     * Starts editing the field under the cursor
+     * or writes the edited value back
+     */
+    pub fn select(&mut self) {
+        if let Some(number) = &mut self.number {
+            if number.active() {
+                number.confirm();
+                return;
+            }
+        }
+
+        let (field, addr) = match self.selected() {
+            Some(selected) => selected,
+            None => return,
+        };
+        let watch_type = field.field_type.watch_type(addr);
+        let mut number = match field.field_type.input(self.x, self.y) {
+            Some(number) => number,
+            None => return,
+        };
+
+        match watch_type.read(&self.memory) {
+            Ok(raw) => {
+                number.open_raw(raw as u32);
+                self.edit_addr = addr;
+                self.number = Some(number);
+            }
+            Err(err) => self.last_error = Some(err),
+        }
+    }
+
+    /// a watch for the field under the cursor
+    pub fn to_watch(&self) -> Option<WatchAddr<T>> {
+        let (field, addr) = self.selected()?;
+        Some(
+            WatchAddr::new(self.x, self.y, field.field_type.watch_type(addr))
+                .with_label(field.name)
+                .with_format(field.field_type.format()),
+        )
+    }
+
+    pub fn open(&mut self) {
+        self.active = true;
+    }
+
+    pub fn close(&mut self, data: T) {
+        self.active = false;
+        self.close_action.activate(data);
+    }
+
+    pub fn back(&mut self, data: T) {
+        if self.editing() {
+            if let Some(number) = &mut self.number {
+                number.cancel();
+            }
+        } else {
+            self.toggle_timer = 0;
+            self.back_action.activate(data);
+        }
+    }
+
+    fn write(&self, addr: usize, number: &NumberInput<T>) -> Result<(), MemoryError> {
+        let raw = number.raw();
+        match number.width() {
+            Width::U8 => self.memory.write_u8(addr, raw as u8),
+            Width::U16 => self.memory.write_u16(addr, raw as u16),
+            Width::U32 => self.memory.write_u32(addr, raw),
+        }
+    }
+
+    /// "name[index] +offset: value labels"
+    fn format_row(&self, row: usize, buffer: &mut [u8]) -> usize {
+        let mut w = TextWriter::new(buffer);
+        let (field, index) = match self.layout.row(row) {
+            Some(row) => row,
+            None => return w.finish(),
+        };
+
+        w.puts(field.name);
+        if field.count > 1 {
+            w.push(b'[');
+            w.dec(index as u64);
+            w.push(b']');
+        }
+        w.puts(" +");
+        w.hex(field.element_offset(index) as u64, 3);
+        w.puts(": ");
+
+        let watch_type = field
+            .field_type
+            .watch_type(self.base + field.element_offset(index));
+        match watch_type.read(&self.memory) {
+            Ok(raw) => {
+                watch_type.format(raw, field.field_type.format(), &mut w);
+                field.write_labels(raw, &mut w);
+            }
+            Err(_) => w.puts("--"),
+        }
+        w.finish()
+    }
+}
+
+impl<T> Drawable<T> for StructView<T>
+where
+    T: Copy + Clone,
+{
+    fn update(&mut self, _data: T) {
+        if self.toggle_timer > 0 {
+            self.toggle_timer -= 1;
+        }
+
+        if !self.active {
+            return;
+        }
+
+        if let Some(mut number) = self.number {
+            if number.take_result() == InputResult::Confirmed {
+                self.last_error = self.write(self.edit_addr, &number).err();
+            }
+            self.number = Some(number).filter(|n| n.active());
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        // header: name and base address
+        let mut line = [0u8; LINE_LEN];
+        let mut w = TextWriter::new(&mut line);
+        w.puts(self.layout.name);
+        w.puts(" @");
+        w.hex(self.base as u64, 8);
+        w.finish();
+        ctxt.set_color(Color::new(0xFF, 0xFF, 0x00, 0xFF));
+        ctxt.putsu8(&line, self.x, self.y);
+
+        let first = self.cursor - self.cursor % self.rows;
+        let last = usize::min(first + self.rows, self.layout.rows());
+        for row in first..last {
+            let y = self.y + ctxt.char_height() * ((row - first) as isize + 1);
+
+            if row == self.cursor {
+                if let Some(number) = self.number.filter(|n| n.active()) {
+                    // the value is replaced by the input while editing
+                    let mut w = TextWriter::new(&mut line);
+                    if let Some((field, _)) = self.layout.row(row) {
+                        w.puts(field.name);
+                    }
+                    w.puts(": ");
+                    let x = self.x + w.finish() as isize * ctxt.char_width();
+                    ctxt.putsu8(&line, self.x, y);
+                    number.draw_at(ctxt, x, y);
+                    continue;
+                }
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+
+            self.format_row(row, &mut line);
+            ctxt.putsu8(&line, self.x, y);
+        }
+    }
+}
+
+impl<T> Widget<T> for StructView<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, data: T) {
+        if self.toggle_timer > 0 {
+            return;
+        }
+
+        self.toggle_timer = self.toggle_timer_max;
+        self.number = None;
+        if self.active {
+            self.close(data);
+        } else {
+            self.open();
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -1,23 +1,48 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(naked_functions)]
 #![feature(asm_experimental_arch)]
 #![feature(asm_const)]
 
+pub mod autosplit;
+pub mod changes;
+pub mod cheat;
+pub mod clock;
 pub mod clone;
 pub mod color;
+pub mod disasm;
 pub mod font;
 pub mod frameadvance;
+pub mod graph;
 pub mod init;
 pub mod input;
+pub mod inputdisplay;
 pub mod interrupt;
+pub mod joybus;
 pub mod keyboard;
+pub mod lag;
+pub mod layout;
+pub mod log;
 pub mod malloc;
 pub mod math;
+pub mod memmap;
 pub mod memory;
 pub mod menu;
 pub mod monitor;
+pub mod movie;
+pub mod numeric;
+pub mod pointer;
+pub mod range;
 pub mod rdp;
 pub mod render;
+pub mod save;
+pub mod search;
+pub mod si;
+pub mod splits;
+pub mod stickcal;
+pub mod telemetry;
+pub mod text;
+pub mod textinput;
 pub mod timer;
+pub mod trigger;
 pub mod usb;
 pub mod watch;
@@ -0,0 +1,122 @@
+use crate::render::{Drawable, RenderContext, Widget};
+use core::marker::PhantomData;
+
+pub const MAX_MESSAGES: usize = 6;
+pub const MESSAGE_LEN: usize = 40;
+
+/**
+ * An on-screen message log
+ * New messages are added at the bottom
+ * and every message disappears after duration frames.
+ * Once the log is full the oldest message is dropped.
+ */
+pub struct MessageLog<T>
+where
+    T: Copy + Clone,
+{
+    pub x: isize,
+    pub y: isize,
+    pub active: bool,
+    /// frames a message stays on screen
+    pub duration: u16,
+    messages: [[u8; MESSAGE_LEN]; MAX_MESSAGES],
+    ages: [u16; MAX_MESSAGES],
+    /// index of the oldest message
+    start: usize,
+    len: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T> MessageLog<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize) -> Self {
+        Self {
+            x,
+            y,
+            active: true,
+            duration: 180,
+            messages: [[0; MESSAGE_LEN]; MAX_MESSAGES],
+            ages: [0; MAX_MESSAGES],
+            start: 0,
+            len: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// adds a null terminated or plain byte string
+    pub fn push(&mut self, message: &[u8]) {
+        if self.len == MAX_MESSAGES {
+            self.start = (self.start + 1) % MAX_MESSAGES;
+            self.len -= 1;
+        }
+
+        let index = (self.start + self.len) % MAX_MESSAGES;
+        let line = &mut self.messages[index];
+        line.fill(0);
+        for (dst, src) in line[..MESSAGE_LEN - 1]
+            .iter_mut()
+            .zip(message.iter().take_while(|c| **c != 0))
+        {
+            *dst = *src;
+        }
+        self.ages[index] = 0;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Drawable<T> for MessageLog<T>
+where
+    T: Copy + Clone,
+{
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        for i in 0..self.len {
+            let index = (self.start + i) % MAX_MESSAGES;
+            let y = self.y + i as isize * (ctxt.char_height() + 2);
+            ctxt.putsu8(&self.messages[index], self.x, y);
+        }
+    }
+
+    fn update(&mut self, _data: T) {
+        for i in 0..self.len {
+            let index = (self.start + i) % MAX_MESSAGES;
+            self.ages[index] = self.ages[index].saturating_add(1);
+        }
+
+        // the oldest message always expires first
+        while self.len > 0 && self.ages[self.start] >= self.duration {
+            self.start = (self.start + 1) % MAX_MESSAGES;
+            self.len -= 1;
+        }
+    }
+}
+
+impl<T> Widget<T> for MessageLog<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -32,3 +32,109 @@ impl<T> Point3<T> {
         return Self { x, y, z };
     }
 }
+
+/// integer square root, rounded down
+pub fn isqrt(value: u32) -> u32 {
+    let mut result = 0u32;
+    let mut bit = 1u32 << 30;
+    let mut value = value;
+
+    while bit > value {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if value >= result + bit {
+            value -= result + bit;
+            result = (result >> 1) + bit;
+        } else {
+            result >>= 1;
+        }
+        bit >>= 2;
+    }
+    result
+}
+
+/// atan(num / den) in degrees for 0 <= num <= den
+fn atan_octant(num: i32, den: i32) -> i32 {
+    // r in 1/1024, atan(r) ~ 45r + 15.64r(1 - r) degrees
+    let r = num * 1024 / den;
+    (45 * r + r * (1024 - r) / 1024 * 1564 / 100 + 512) / 1024
+}
+
+/**
+ * Angle of a vector in degrees (0 to 359)
+ * 0 points right, 90 points up (positive y)
+ * the approximation is about a quarter degree off,
+ * the rounded result is within a degree
+ */
+pub fn angle(x: i32, y: i32) -> u16 {
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0 && ay == 0 {
+        return 0;
+    }
+
+    let a = if ay <= ax {
+        atan_octant(ay, ax)
+    } else {
+        90 - atan_octant(ax, ay)
+    };
+
+    let degrees = match (x >= 0, y >= 0) {
+        (true, true) => a,
+        (false, true) => 180 - a,
+        (false, false) => 180 + a,
+        (true, false) => 360 - a,
+    };
+    (degrees % 360) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_roots() {
+        for (value, root) in [(0, 0), (1, 1), (3, 1), (4, 2), (15, 3), (16, 4), (6400, 80)] {
+            assert_eq!(isqrt(value), root);
+        }
+        assert_eq!(isqrt(u32::MAX), 0xFFFF);
+        for root in 0..1000u32 {
+            assert_eq!(isqrt(root * root), root);
+            assert_eq!(isqrt((root + 1) * (root + 1) - 1), root);
+        }
+    }
+
+    #[test]
+    fn axes_and_diagonals() {
+        assert_eq!(angle(0, 0), 0);
+        assert_eq!(angle(80, 0), 0);
+        assert_eq!(angle(0, 80), 90);
+        assert_eq!(angle(-80, 0), 180);
+        assert_eq!(angle(0, -80), 270);
+
+        assert_eq!(angle(57, 57), 45);
+        assert_eq!(angle(-57, 57), 135);
+        assert_eq!(angle(-57, -57), 225);
+        assert_eq!(angle(57, -57), 315);
+    }
+
+    #[test]
+    fn every_quadrant_within_a_degree() {
+        for x in -128..128 {
+            for y in -128..128 {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+                let exact = (y as f64).atan2(x as f64).to_degrees().rem_euclid(360.0);
+                let error = (angle(x, y) as f64 - exact + 180.0).rem_euclid(360.0) - 180.0;
+                assert!(error.abs() < 1.0, "{} {}: {}", x, y, angle(x, y));
+            }
+        }
+
+        assert_eq!(angle(80, 20), 14);
+        assert_eq!(angle(-20, 80), 104);
+        assert_eq!(angle(-80, -20), 194);
+        assert_eq!(angle(20, -80), 284);
+    }
+}
@@ -0,0 +1,449 @@
+use core::cell::Cell;
+use core::ptr::{read_volatile, write_volatile};
+
+/**
+ * Describes the n64 memory map
+ * and provides checked access to it.
+ * Addresses are virtual KSEG0 (cached) or KSEG1 (uncached)
+ * addresses, both mirror the same physical memory.
+ * Everything else (KUSEG, KSEG2) is tlb mapped and
+ * considered unreadable.
+ */
+
+pub const KSEG0: usize = 0x80000000;
+pub const KSEG1: usize = 0xA0000000;
+const KSEG_END: usize = 0xC0000000;
+pub const PHYS_MASK: usize = 0x1FFFFFFF;
+
+pub const RDRAM_SIZE: usize = 0x400000;
+pub const RDRAM_SIZE_EXPANDED: usize = 0x800000;
+
+/// osMemSize is written by ipl3 during boot
+const OS_MEM_SIZE: *const u32 = 0x80000318 as *const u32;
+
+const PI_STATUS_REG: *const u32 = 0xA4600010 as *const u32;
+const PI_STATUS_BUSY: u32 = 0x3;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Access {
+    None,
+    Read,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn readable(&self) -> bool {
+        *self != Access::None
+    }
+
+    pub fn writable(&self) -> bool {
+        *self == Access::ReadWrite
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RegionKind {
+    Rdram,
+    SpDmem,
+    SpImem,
+    PifRom,
+    PifRam,
+    CartRom,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MemoryError {
+    /// the address is not backed by a known region
+    Unmapped,
+    /// the region does not allow this access
+    Denied,
+    /// word access to an unaligned address
+    Unaligned,
+}
+
+/**
+ * A physical memory region
+ * end is exclusive
+ */
+#[derive(Copy, Clone)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub name: &'static str,
+    pub start: usize,
+    pub end: usize,
+    pub access: Access,
+}
+
+impl Region {
+    pub const fn new(
+        kind: RegionKind,
+        name: &'static str,
+        start: usize,
+        end: usize,
+        access: Access,
+    ) -> Self {
+        Self {
+            kind,
+            name,
+            start,
+            end,
+            access,
+        }
+    }
+
+    pub fn contains(&self, phys: usize) -> bool {
+        phys >= self.start && phys < self.end
+    }
+
+    /**
+     * The rcp and pif only answer 32 bit reads
+     * and cart rom is read through the pi
+     * smaller accesses have to be emulated
+     */
+    pub fn word_access_only(&self) -> bool {
+        self.kind != RegionKind::Rdram
+    }
+}
+
+pub const SP_DMEM: Region = Region::new(
+    RegionKind::SpDmem,
+    "SP DMEM",
+    0x04000000,
+    0x04001000,
+    Access::ReadWrite,
+);
+pub const SP_IMEM: Region = Region::new(
+    RegionKind::SpImem,
+    "SP IMEM",
+    0x04001000,
+    0x04002000,
+    Access::ReadWrite,
+);
+pub const CART_ROM: Region = Region::new(
+    RegionKind::CartRom,
+    "ROM",
+    0x10000000,
+    0x1FC00000,
+    Access::Read,
+);
+/// the pif rom is locked after boot
+pub const PIF_ROM: Region = Region::new(
+    RegionKind::PifRom,
+    "PIF ROM",
+    0x1FC00000,
+    0x1FC007C0,
+    Access::None,
+);
+pub const PIF_RAM: Region = Region::new(
+    RegionKind::PifRam,
+    "PIF RAM",
+    0x1FC007C0,
+    0x1FC00800,
+    Access::ReadWrite,
+);
+
+/**
+ * Checked memory access
+ * Implemented by MemoryMap for the real hardware,
+ * anything else (e.g. a plain buffer) can implement it as well
+ */
+pub trait MemoryAccess {
+    fn read_u8(&self, addr: usize) -> Result<u8, MemoryError>;
+    fn read_u16(&self, addr: usize) -> Result<u16, MemoryError>;
+    fn read_u32(&self, addr: usize) -> Result<u32, MemoryError>;
+    fn write_u8(&self, addr: usize, value: u8) -> Result<(), MemoryError>;
+    fn write_u16(&self, addr: usize, value: u16) -> Result<(), MemoryError>;
+    fn write_u32(&self, addr: usize, value: u32) -> Result<(), MemoryError>;
+}
+
+#[derive(Copy, Clone)]
+pub struct MemoryMap {
+    pub rdram_size: usize,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self::new(RDRAM_SIZE)
+    }
+}
+
+impl MemoryMap {
+    pub fn new(rdram_size: usize) -> Self {
+        Self { rdram_size }
+    }
+
+    /**
+     * Uses osMemSize to check for an expansion pak
+     * Falls back to 4MB if the value does not make sense
+     */
+    pub fn detect() -> Self {
+        let size = unsafe { read_volatile(OS_MEM_SIZE) } as usize;
+        if size == RDRAM_SIZE_EXPANDED {
+            Self::new(RDRAM_SIZE_EXPANDED)
+        } else {
+            Self::new(RDRAM_SIZE)
+        }
+    }
+
+    pub fn expansion_pak(&self) -> bool {
+        self.rdram_size >= RDRAM_SIZE_EXPANDED
+    }
+
+    pub fn rdram(&self) -> Region {
+        Region::new(
+            RegionKind::Rdram,
+            "RDRAM",
+            0,
+            self.rdram_size,
+            Access::ReadWrite,
+        )
+    }
+
+    /// converts a KSEG0 or KSEG1 address to a physical address
+    pub fn to_physical(addr: usize) -> Option<usize> {
+        if (KSEG0..KSEG_END).contains(&addr) {
+            Some(addr & PHYS_MASK)
+        } else {
+            None
+        }
+    }
+
+    /// returns the region a virtual address belongs to
+    pub fn region(&self, addr: usize) -> Option<Region> {
+        let phys = Self::to_physical(addr)?;
+
+        [self.rdram(), SP_DMEM, SP_IMEM, CART_ROM, PIF_ROM, PIF_RAM]
+            .iter()
+            .find(|r| r.contains(phys))
+            .copied()
+    }
+
+    /// checks that len bytes starting at addr are in a single region with the given access
+    fn check(&self, addr: usize, len: usize, write: bool) -> Result<Region, MemoryError> {
+        let region = self.region(addr).ok_or(MemoryError::Unmapped)?;
+        let phys = addr & PHYS_MASK;
+
+        if len > 0 && !region.contains(phys + len - 1) {
+            return Err(MemoryError::Unmapped);
+        }
+
+        let allowed = if write {
+            region.access.writable()
+        } else {
+            region.access.readable()
+        };
+
+        if allowed {
+            Ok(region)
+        } else {
+            Err(MemoryError::Denied)
+        }
+    }
+
+    pub fn can_read(&self, addr: usize, len: usize) -> bool {
+        self.check(addr, len, false).is_ok()
+    }
+
+    pub fn can_write(&self, addr: usize, len: usize) -> bool {
+        self.check(addr, len, true).is_ok()
+    }
+
+    /**
+     * Only rdram is accessed through the caller's address,
+     * everything else is hardware and always accessed uncached
+     */
+    fn hw_addr(region: &Region, addr: usize) -> usize {
+        if region.kind == RegionKind::Rdram {
+            addr
+        } else {
+            (addr & PHYS_MASK) | KSEG1
+        }
+    }
+
+    /// cart rom can only be read while the pi is idle
+    unsafe fn raw_read_u32(region: &Region, addr: usize) -> u32 {
+        if region.kind == RegionKind::CartRom {
+            while read_volatile(PI_STATUS_REG) & PI_STATUS_BUSY != 0 {}
+        }
+        read_volatile(Self::hw_addr(region, addr) as *const u32)
+    }
+
+    unsafe fn raw_write_u32(region: &Region, addr: usize, value: u32) {
+        write_volatile(Self::hw_addr(region, addr) as *mut u32, value)
+    }
+
+    pub fn read_u32(&self, addr: usize) -> Result<u32, MemoryError> {
+        if addr % 4 != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+        let region = self.check(addr, 4, false)?;
+        Ok(unsafe { Self::raw_read_u32(&region, addr) })
+    }
+
+    pub fn read_u16(&self, addr: usize) -> Result<u16, MemoryError> {
+        if addr % 2 != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+        let region = self.check(addr, 2, false)?;
+
+        if region.word_access_only() {
+            let word = unsafe { Self::raw_read_u32(&region, addr & !3) };
+            Ok((word >> ((2 - (addr & 2)) * 8)) as u16)
+        } else {
+            Ok(unsafe { read_volatile(addr as *const u16) })
+        }
+    }
+
+    pub fn read_u8(&self, addr: usize) -> Result<u8, MemoryError> {
+        let region = self.check(addr, 1, false)?;
+
+        if region.word_access_only() {
+            let word = unsafe { Self::raw_read_u32(&region, addr & !3) };
+            Ok((word >> ((3 - (addr & 3)) * 8)) as u8)
+        } else {
+            Ok(unsafe { read_volatile(addr as *const u8) })
+        }
+    }
+
+    pub fn write_u32(&self, addr: usize, value: u32) -> Result<(), MemoryError> {
+        if addr % 4 != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+        let region = self.check(addr, 4, true)?;
+        unsafe { Self::raw_write_u32(&region, addr, value) };
+        Ok(())
+    }
+
+    pub fn write_u16(&self, addr: usize, value: u16) -> Result<(), MemoryError> {
+        if addr % 2 != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+        let region = self.check(addr, 2, true)?;
+
+        if region.word_access_only() {
+            let shift = (2 - (addr & 2)) * 8;
+            let word = unsafe { Self::raw_read_u32(&region, addr & !3) };
+            let word = (word & !(0xFFFF << shift)) | ((value as u32) << shift);
+            unsafe { Self::raw_write_u32(&region, addr & !3, word) };
+        } else {
+            unsafe { write_volatile(addr as *mut u16, value) };
+        }
+        Ok(())
+    }
+
+    pub fn write_u8(&self, addr: usize, value: u8) -> Result<(), MemoryError> {
+        let region = self.check(addr, 1, true)?;
+
+        if region.word_access_only() {
+            let shift = (3 - (addr & 3)) * 8;
+            let word = unsafe { Self::raw_read_u32(&region, addr & !3) };
+            let word = (word & !(0xFF << shift)) | ((value as u32) << shift);
+            unsafe { Self::raw_write_u32(&region, addr & !3, word) };
+        } else {
+            unsafe { write_volatile(addr as *mut u8, value) };
+        }
+        Ok(())
+    }
+}
+
+impl MemoryAccess for MemoryMap {
+    fn read_u8(&self, addr: usize) -> Result<u8, MemoryError> {
+        MemoryMap::read_u8(self, addr)
+    }
+
+    fn read_u16(&self, addr: usize) -> Result<u16, MemoryError> {
+        MemoryMap::read_u16(self, addr)
+    }
+
+    fn read_u32(&self, addr: usize) -> Result<u32, MemoryError> {
+        MemoryMap::read_u32(self, addr)
+    }
+
+    fn write_u8(&self, addr: usize, value: u8) -> Result<(), MemoryError> {
+        MemoryMap::write_u8(self, addr, value)
+    }
+
+    fn write_u16(&self, addr: usize, value: u16) -> Result<(), MemoryError> {
+        MemoryMap::write_u16(self, addr, value)
+    }
+
+    fn write_u32(&self, addr: usize, value: u32) -> Result<(), MemoryError> {
+        MemoryMap::write_u32(self, addr, value)
+    }
+}
+
+/**
+ * MemoryAccess over a plain buffer that stands in
+ * for the physical memory starting at base
+ * KSEG0 and KSEG1 addresses of the same byte both work,
+ * values are big endian like on the console.
+ */
+pub struct BufferMemory<'a> {
+    base: usize,
+    data: &'a [Cell<u8>],
+}
+
+impl<'a> BufferMemory<'a> {
+    pub fn new(base: usize, data: &'a mut [u8]) -> Self {
+        Self {
+            base: base & PHYS_MASK,
+            data: Cell::from_mut(data).as_slice_of_cells(),
+        }
+    }
+
+    /// offset of len bytes at addr in the buffer
+    fn offset(&self, addr: usize, len: usize) -> Result<usize, MemoryError> {
+        if addr % len != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+
+        let phys = MemoryMap::to_physical(addr).ok_or(MemoryError::Unmapped)?;
+        match phys.checked_sub(self.base) {
+            Some(offset) if offset + len <= self.data.len() => Ok(offset),
+            _ => Err(MemoryError::Unmapped),
+        }
+    }
+
+    fn read<const N: usize>(&self, addr: usize) -> Result<[u8; N], MemoryError> {
+        let offset = self.offset(addr, N)?;
+        let mut bytes = [0u8; N];
+        for (b, cell) in bytes.iter_mut().zip(&self.data[offset..offset + N]) {
+            *b = cell.get();
+        }
+        Ok(bytes)
+    }
+
+    fn write<const N: usize>(&self, addr: usize, bytes: [u8; N]) -> Result<(), MemoryError> {
+        let offset = self.offset(addr, N)?;
+        for (b, cell) in bytes.iter().zip(&self.data[offset..offset + N]) {
+            cell.set(*b);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> MemoryAccess for BufferMemory<'a> {
+    fn read_u8(&self, addr: usize) -> Result<u8, MemoryError> {
+        Ok(u8::from_be_bytes(self.read(addr)?))
+    }
+
+    fn read_u16(&self, addr: usize) -> Result<u16, MemoryError> {
+        Ok(u16::from_be_bytes(self.read(addr)?))
+    }
+
+    fn read_u32(&self, addr: usize) -> Result<u32, MemoryError> {
+        Ok(u32::from_be_bytes(self.read(addr)?))
+    }
+
+    fn write_u8(&self, addr: usize, value: u8) -> Result<(), MemoryError> {
+        self.write(addr, value.to_be_bytes())
+    }
+
+    fn write_u16(&self, addr: usize, value: u16) -> Result<(), MemoryError> {
+        self.write(addr, value.to_be_bytes())
+    }
+
+    fn write_u32(&self, addr: usize, value: u32) -> Result<(), MemoryError> {
+        self.write(addr, value.to_be_bytes())
+    }
+}
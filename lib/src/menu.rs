@@ -1,6 +1,11 @@
+use crate::cheat::CheatMenu;
 use crate::color::Color;
+use crate::input::{Button, InputHandler};
+use crate::layout::StructView;
 use crate::monitor::Monitor;
+use crate::numeric::NumberInput;
 use crate::render::Drawable;
+use crate::search::CheatFinder;
 
 use super::font::*;
 use super::math::*;
@@ -14,6 +19,7 @@ pub const MAX_TITLE_LEN: usize = 15;
  * This is a simple windowing system
  * It holds any menu-like object for easy switching
  */
+#[allow(clippy::large_enum_variant)]
 pub enum MenuFocus<T>
 where
     T: Copy + Clone,
@@ -21,6 +27,9 @@ where
     Menu(Menu<T>),
     Monitor(Monitor<T>),
     FrameAdvance(FrameAdvance<T>),
+    Cheats(CheatMenu<T>),
+    Search(CheatFinder<T>),
+    Struct(StructView<T>),
 }
 
 impl<T> Widget<T> for MenuFocus<T>
@@ -32,6 +41,9 @@ where
             Self::Menu(m) => m.toggle(data),
             Self::Monitor(m) => m.toggle(data),
             Self::FrameAdvance(m) => m.toggle(data),
+            Self::Cheats(m) => m.toggle(data),
+            Self::Search(m) => m.toggle(data),
+            Self::Struct(m) => m.toggle(data),
         }
     }
 
@@ -40,6 +52,9 @@ where
             Self::Menu(m) => m.active(),
             Self::Monitor(m) => m.active(),
             Self::FrameAdvance(m) => m.active(),
+            Self::Cheats(m) => m.active(),
+            Self::Search(m) => m.active(),
+            Self::Struct(m) => m.active(),
         }
     }
 }
@@ -53,6 +68,9 @@ where
             Self::Menu(m) => m.update(data),
             Self::Monitor(m) => m.update(data),
             Self::FrameAdvance(m) => m.update(data),
+            Self::Cheats(m) => m.update(data),
+            Self::Search(m) => m.update(data),
+            Self::Struct(m) => m.update(data),
         }
     }
 
@@ -61,6 +79,9 @@ where
             Self::Menu(m) => m.draw(ctxt),
             Self::Monitor(m) => m.draw(ctxt),
             Self::FrameAdvance(m) => m.draw(ctxt),
+            Self::Cheats(m) => m.draw(ctxt),
+            Self::Search(m) => m.draw(ctxt),
+            Self::Struct(m) => m.draw(ctxt),
         }
     }
 }
@@ -71,6 +92,7 @@ where
     T: Copy + Clone,
 {
     Button(Entry<T>),
+    Spinner(Spinner<T>),
 }
 
 impl<T> EntryTypes<T>
@@ -80,24 +102,66 @@ where
     pub fn activate(&mut self, data: T) {
         match self {
             EntryTypes::Button(b) => b.activate(data),
+            EntryTypes::Spinner(s) => s.activate(data),
         }
     }
 
     pub fn active(&self) -> bool {
         match self {
             EntryTypes::Button(b) => b.active,
+            EntryTypes::Spinner(s) => s.active,
         }
     }
 
     pub fn draw(&mut self, ctxt: &mut dyn RenderContext, x: isize, y: isize) {
         match self {
             EntryTypes::Button(b) => b.draw(ctxt, x, y),
+            EntryTypes::Spinner(s) => s.draw(ctxt, x, y),
         }
     }
 
     pub fn call_update(&mut self, data: T) {
         match self {
             EntryTypes::Button(b) => b.call_update(data),
+            EntryTypes::Spinner(s) => s.call_update(data),
+        }
+    }
+
+    /// the entry wants the directional input for itself
+    pub fn editing(&self) -> bool {
+        match self {
+            EntryTypes::Button(_) => false,
+            EntryTypes::Spinner(s) => s.input.active(),
+        }
+    }
+
+    pub fn up(&mut self) {
+        if let EntryTypes::Spinner(s) = self {
+            s.input.up();
+        }
+    }
+
+    pub fn down(&mut self) {
+        if let EntryTypes::Spinner(s) = self {
+            s.input.down();
+        }
+    }
+
+    pub fn left(&mut self) {
+        if let EntryTypes::Spinner(s) = self {
+            s.input.left();
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let EntryTypes::Spinner(s) = self {
+            s.input.right();
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if let EntryTypes::Spinner(s) = self {
+            s.input.cancel();
         }
     }
 }
@@ -207,6 +271,76 @@ where
     }
 }
 
+pub type SpinnerFn<T> = fn(spinner: &mut Spinner<T>, data: T) -> Option<usize>;
+
+pub fn spinner_no_op<T: Copy + Clone>(_spinner: &mut Spinner<T>, _data: T) -> Option<usize> {
+    None
+}
+
+/**
+ * A menu entry with a number next to its title
+ * Activating it starts editing the number,
+ * activating it again confirms and calls the action
+ * which can read the new value from the input
+ */
+#[derive(Clone, Copy)]
+pub struct Spinner<T>
+where
+    T: Copy + Clone,
+{
+    pub title: [char; MAX_TITLE_LEN],
+    pub active: bool,
+    pub input: NumberInput<T>,
+    pub update: SpinnerFn<T>,
+    pub action: SpinnerFn<T>,
+}
+
+impl<T> Spinner<T>
+where
+    T: Copy + Clone,
+{
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        title: &str,
+        input: NumberInput<T>,
+        update: SpinnerFn<T>,
+        action: SpinnerFn<T>,
+    ) -> EntryTypes<T> {
+        let mut title_ca = ['\0'; MAX_TITLE_LEN];
+        for (dst, src) in title_ca.iter_mut().zip(title.chars()) {
+            *dst = src;
+        }
+
+        EntryTypes::Spinner(Self {
+            title: title_ca,
+            input,
+            update,
+            action,
+            active: true,
+        })
+    }
+
+    pub fn draw(&mut self, ctxt: &mut dyn RenderContext, x: isize, y: isize) {
+        ctxt.cputs(&self.title, x, y);
+        let len = self.title.iter().take_while(|c| **c != '\0').count() as isize;
+        let value_x = x + (len + 1) * ctxt.char_width();
+        self.input.draw_at(ctxt, value_x, y);
+    }
+
+    pub fn call_update(&mut self, data: T) {
+        (self.update)(self, data);
+    }
+
+    pub fn activate(&mut self, data: T) {
+        if self.input.active() {
+            self.input.confirm();
+            (self.action)(self, data);
+        } else {
+            self.input.open(self.input.value());
+        }
+    }
+}
+
 pub struct Menu<T>
 where
     T: Copy + Clone,
@@ -263,6 +397,11 @@ where
     }
 
     pub fn inc_cursor(&mut self) {
+        if self.editing() {
+            self.entries[self.cursor as usize].down();
+            return;
+        }
+
         self.cursor += 1;
         if self.cursor >= self.active_entries as isize {
             self.cursor = 0;
@@ -270,12 +409,35 @@ where
     }
 
     pub fn dec_cursor(&mut self) {
+        if self.editing() {
+            self.entries[self.cursor as usize].up();
+            return;
+        }
+
         self.cursor -= 1;
         if self.cursor < 0 {
             self.cursor = self.active_entries as isize - 1;
         }
     }
 
+    fn editing(&self) -> bool {
+        self.active_entries > 0 && self.entries[self.cursor as usize].editing()
+    }
+
+    /// selects the more significant digit of a spinner that is being edited
+    pub fn left(&mut self) {
+        if self.editing() {
+            self.entries[self.cursor as usize].left();
+        }
+    }
+
+    /// selects the less significant digit of a spinner that is being edited
+    pub fn right(&mut self) {
+        if self.editing() {
+            self.entries[self.cursor as usize].right();
+        }
+    }
+
     pub fn activate(&mut self, data: T) {
         if !self.active {
             return;
@@ -284,6 +446,31 @@ where
         self.entries[self.cursor as usize].activate(data);
     }
 
+    /**
+     * Moves through the menu with the d-pad or the stick,
+     * A activates the entry and B goes back
+     * The stick only counts past the input's menu deadzone.
+     */
+    pub fn navigate(&mut self, data: T, input: &InputHandler) {
+        if !self.active {
+            return;
+        }
+
+        if input.read_nav(Button::DpadDown, true) {
+            self.inc_cursor();
+        } else if input.read_nav(Button::DpadUp, true) {
+            self.dec_cursor();
+        } else if input.read_nav(Button::DpadLeft, true) {
+            self.left();
+        } else if input.read_nav(Button::DpadRight, true) {
+            self.right();
+        } else if input.read_button(Button::AInput, true) {
+            self.activate(data);
+        } else if input.read_button(Button::BInput, true) {
+            self.back(data);
+        }
+    }
+
     pub fn open(&mut self, data: T) {
         self.active = true;
         self.open_action.activate(data);
@@ -295,6 +482,11 @@ where
     }
 
     pub fn back(&mut self, data: T) {
+        if self.editing() {
+            self.entries[self.cursor as usize].cancel();
+            return;
+        }
+
         self.toggle_timer = 0;
         self.back_action.activate(data);
     }
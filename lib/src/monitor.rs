@@ -1,4 +1,11 @@
+use crate::changes::ChangeTracker;
+use crate::disasm::{disassemble, Instruction};
 use crate::keyboard::{self, Keyboard, HEX};
+use crate::memmap::{MemoryError, MemoryMap, KSEG0};
+use crate::numeric::{Base, NumberInput, Width};
+use crate::range::{self, Range, RangeError, UndoBuffer};
+use crate::text::draw_hex;
+use crate::textinput::InputResult;
 use embedgdb::Stream;
 
 use super::color::Color;
@@ -7,6 +14,76 @@ use super::render::{Drawable, RenderContext, Widget};
 use core::ffi::c_void;
 use embedgdb::{BufferedStream, Parser};
 
+/**
+ * How the monitor displays memory
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub enum MonitorMode {
+    Hex,
+    Ascii,
+    /// one instruction per row
+    Disasm,
+    /// lists bytes in the page that changed recently
+    Changes,
+}
+
+pub const HISTORY_LEN: usize = 16;
+
+/// keyboard tags for the different inputs
+const INPUT_ADDR: u8 = 0;
+const INPUT_FILL: u8 = 2;
+const INPUT_COPY: u8 = 3;
+
+/**
+ * A bounded stack of addresses
+ * pushing onto a full stack drops the oldest entry
+ */
+pub struct AddrStack {
+    entries: [usize; HISTORY_LEN],
+    len: usize,
+}
+
+impl AddrStack {
+    pub fn new() -> Self {
+        Self {
+            entries: [0; HISTORY_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, addr: usize) {
+        if self.len == HISTORY_LEN {
+            self.entries.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.entries[self.len] = addr;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            Some(self.entries[self.len])
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for AddrStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /**
  * This is a memory monitor
  * that can browse wram
@@ -31,7 +108,18 @@ where
     cursor_y: usize,
     addr_buffer: [u8; 8],
     keyboard: Keyboard<'static, T>,
-    ascii_mode: bool,
+    number: NumberInput<T>,
+    /// address the number input writes to
+    value_addr: usize,
+    mode: MonitorMode,
+    pub memory: MemoryMap,
+    pub changes: ChangeTracker,
+    back_history: AddrStack,
+    forward_history: AddrStack,
+    selection_start: Option<usize>,
+    selection_end: usize,
+    undo: UndoBuffer,
+    pub last_error: Option<RangeError>,
 }
 
 impl<T> Monitor<T>
@@ -61,12 +149,28 @@ where
             cursor_y: 0,
             addr_buffer: [0; 8],
             keyboard: Keyboard::new(x, y, &HEX),
-            ascii_mode: false,
+            number: NumberInput::new(x, y, Base::Hex, Width::U8, false),
+            value_addr: 0,
+            mode: MonitorMode::Hex,
+            memory: MemoryMap::detect(),
+            changes: ChangeTracker::new(30),
+            back_history: AddrStack::new(),
+            forward_history: AddrStack::new(),
+            selection_start: None,
+            selection_end: 0,
+            undo: UndoBuffer::new(),
+            last_error: None,
         }
     }
 
     pub fn left(&mut self) {
-        if !self.keyboard.active() {
+        if self.mode == MonitorMode::Disasm {
+            return;
+        }
+
+        if self.number.active() {
+            self.number.left();
+        } else if !self.keyboard.active() {
             if self.cursor_x == 0 {
                 self.cursor_x = self.bytes_per_row - 1;
             } else {
@@ -78,7 +182,13 @@ where
     }
 
     pub fn right(&mut self) {
-        if !self.keyboard.active() {
+        if self.mode == MonitorMode::Disasm {
+            return;
+        }
+
+        if self.number.active() {
+            self.number.right();
+        } else if !self.keyboard.active() {
             self.cursor_x += 1;
             if self.cursor_x >= self.bytes_per_row {
                 self.cursor_x = 0;
@@ -89,7 +199,9 @@ where
     }
 
     pub fn up(&mut self) {
-        if !self.keyboard.active() {
+        if self.number.active() {
+            self.number.up();
+        } else if !self.keyboard.active() {
             if self.cursor_y == 0 {
                 self.cursor_y = self.rows - 1;
             } else {
@@ -101,7 +213,9 @@ where
     }
 
     pub fn down(&mut self) {
-        if !self.keyboard.active() {
+        if self.number.active() {
+            self.number.down();
+        } else if !self.keyboard.active() {
             self.cursor_y += 1;
             if self.cursor_y >= self.rows {
                 self.cursor_y = 0;
@@ -113,38 +227,30 @@ where
 
     pub fn inc_addr(&mut self) {
         if !self.keyboard.active() {
-            self.addr = unsafe { self.addr.add(self.bytes_per_row * self.rows) };
+            self.addr = unsafe { self.addr.add(self.page_size()) };
         }
     }
 
     pub fn dec_addr(&mut self) {
         if !self.keyboard.active() {
-            self.addr = unsafe { self.addr.sub(self.bytes_per_row * self.rows) };
+            self.addr = unsafe { self.addr.sub(self.page_size()) };
         }
     }
 
     pub fn inc_value(&mut self) {
         if !self.keyboard.active() {
-            let addr = unsafe {
-                self.addr
-                    .add(self.calc_offset(self.cursor_x, self.cursor_y))
-            };
-
-            unsafe {
-                *(addr as *mut u8) += 1;
+            let addr = self.cursor_addr();
+            if let Ok(value) = self.memory.read_u8(addr) {
+                let _ = self.memory.write_u8(addr, value.wrapping_add(1));
             }
         }
     }
 
     pub fn dec_value(&mut self) {
         if !self.keyboard.active() {
-            let addr = unsafe {
-                self.addr
-                    .add(self.calc_offset(self.cursor_x, self.cursor_y))
-            };
-
-            unsafe {
-                *(addr as *mut u8) -= 1;
+            let addr = self.cursor_addr();
+            if let Ok(value) = self.memory.read_u8(addr) {
+                let _ = self.memory.write_u8(addr, value.wrapping_sub(1));
             }
         }
     }
@@ -156,7 +262,9 @@ where
     }
 
     pub fn enter(&mut self) {
-        if self.keyboard.active() {
+        if self.number.active() {
+            self.number.confirm();
+        } else if self.keyboard.active() {
             self.keyboard.enter();
         } else {
             self.value_input();
@@ -164,17 +272,218 @@ where
     }
 
     pub fn toggle_ascii(&mut self) {
-        self.ascii_mode = !self.ascii_mode;
+        self.mode = if self.mode == MonitorMode::Ascii {
+            MonitorMode::Hex
+        } else {
+            MonitorMode::Ascii
+        };
+    }
+
+    /**
+     * Switches between the disassembly view and the hex view
+     * instructions are word aligned so the address is aligned as well
+     */
+    pub fn toggle_disasm(&mut self) {
+        if self.mode == MonitorMode::Disasm {
+            self.mode = MonitorMode::Hex;
+        } else {
+            self.mode = MonitorMode::Disasm;
+            self.cursor_x = 0;
+            self.addr = (self.addr as usize & !3) as *mut c_void;
+        }
+    }
+
+    /**
+     * Switches between the list of changed bytes and the hex view
+     */
+    pub fn toggle_changes(&mut self) {
+        self.mode = if self.mode == MonitorMode::Changes {
+            MonitorMode::Hex
+        } else {
+            MonitorMode::Changes
+        };
+    }
+
+    /**
+     * Moves the monitor to addr and records
+     * the current address in the history
+     */
+    pub fn goto(&mut self, addr: usize) {
+        self.back_history.push(self.addr as usize);
+        self.forward_history.clear();
+        self.set_addr(addr);
+    }
+
+    fn set_addr(&mut self, addr: usize) {
+        let addr = if self.mode == MonitorMode::Disasm {
+            addr & !3
+        } else {
+            addr
+        };
+
+        self.addr = addr as *mut c_void;
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+    }
+
+    /**
+     * Address the word under the cursor points to
+     * In disassembly mode this is the jump or branch target,
+     * otherwise the word has to look like a KSEG0 pointer into rdram
+     */
+    pub fn pointer_at_cursor(&self) -> Option<usize> {
+        let addr = self.cursor_addr() & !3;
+        let word = self.memory.read_u32(addr).ok()?;
+
+        if self.mode == MonitorMode::Disasm {
+            return Instruction::new(addr as u32, word)
+                .target()
+                .map(|target| target as usize);
+        }
+
+        let target = word as usize;
+        if target >= KSEG0 && target < KSEG0 + self.memory.rdram_size {
+            Some(target)
+        } else {
+            None
+        }
+    }
+
+    /// jumps to the pointer under the cursor
+    pub fn follow(&mut self) {
+        if self.keyboard.active() {
+            return;
+        }
+
+        if let Some(target) = self.pointer_at_cursor() {
+            self.goto(target);
+        }
+    }
+
+    pub fn history_back(&mut self) {
+        if self.keyboard.active() {
+            return;
+        }
+
+        if let Some(addr) = self.back_history.pop() {
+            self.forward_history.push(self.addr as usize);
+            self.set_addr(addr);
+        }
+    }
+
+    pub fn history_forward(&mut self) {
+        if self.keyboard.active() {
+            return;
+        }
+
+        if let Some(addr) = self.forward_history.pop() {
+            self.back_history.push(self.addr as usize);
+            self.set_addr(addr);
+        }
+    }
+
+    pub fn mode(&self) -> MonitorMode {
+        self.mode
     }
 
     pub fn addr_input(&mut self) {
-        self.keyboard.reset(&mut self.addr_buffer, 0);
+        self.keyboard.reset(&mut self.addr_buffer, INPUT_ADDR);
         self.keyboard.active = true;
     }
 
+    /**
+     * Edits the value at the cursor
+     * in the current value width
+     */
     pub fn value_input(&mut self) {
-        self.keyboard.reset(&mut self.addr_buffer, 1);
-        self.keyboard.active = true;
+        let addr = self.cursor_addr();
+        let value = match self.number.width() {
+            Width::U8 => self.memory.read_u8(addr).map(|value| value as u32),
+            Width::U16 => self.memory.read_u16(addr).map(|value| value as u32),
+            Width::U32 => self.memory.read_u32(addr),
+        };
+
+        match value {
+            Ok(value) => {
+                self.value_addr = addr;
+                self.number.open_raw(value);
+            }
+            Err(err) => self.last_error = Some(RangeError::Memory(err)),
+        }
+    }
+
+    /// switches between editing 8, 16 and 32 bit values
+    pub fn cycle_value_width(&mut self) {
+        if !self.number.active() {
+            self.number.set_width(self.number.width().next());
+        }
+    }
+
+    pub fn value_width(&self) -> Width {
+        self.number.width()
+    }
+
+    fn write_value(&self, addr: usize, value: u32) -> Result<(), MemoryError> {
+        match self.number.width() {
+            Width::U8 => self.memory.write_u8(addr, value as u8),
+            Width::U16 => self.memory.write_u16(addr, value as u16),
+            Width::U32 => self.memory.write_u32(addr, value),
+        }
+    }
+
+    /**
+     * Selects a range
+     * the first call marks the start at the cursor,
+     * every following call moves the end to the cursor
+     */
+    pub fn mark(&mut self) {
+        if self.keyboard.active() {
+            return;
+        }
+
+        if self.selection_start.is_none() {
+            self.selection_start = Some(self.cursor_addr());
+        }
+        self.selection_end = self.cursor_addr();
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_start = None;
+    }
+
+    pub fn selection(&self) -> Option<Range> {
+        self.selection_start
+            .map(|start| Range::new(start, self.selection_end))
+    }
+
+    /// asks for the value the selection is filled with
+    pub fn fill_input(&mut self) {
+        if self.selection().is_some() {
+            self.keyboard.reset(&mut self.addr_buffer, INPUT_FILL);
+            self.keyboard.active = true;
+        }
+    }
+
+    /// asks for the address the selection is copied to
+    pub fn copy_input(&mut self) {
+        if self.selection().is_some() {
+            self.keyboard.reset(&mut self.addr_buffer, INPUT_COPY);
+            self.keyboard.active = true;
+        }
+    }
+
+    /// sends the selection to the host
+    pub fn dump_selection(&mut self) {
+        if let Some(selection) = self.selection() {
+            self.last_error = range::dump(&self.memory, selection).err();
+        }
+    }
+
+    /// reverts the last fill or copy
+    pub fn undo(&mut self) {
+        if !self.keyboard.active() {
+            self.last_error = self.undo.restore(&self.memory).err();
+        }
     }
 
     pub fn open(&mut self, data: T) {
@@ -188,7 +497,9 @@ where
     }
 
     pub fn back(&mut self, data: T) {
-        if self.keyboard.active() {
+        if self.number.active() {
+            self.number.cancel();
+        } else if self.keyboard.active() {
             self.keyboard.back(&mut self.addr_buffer);
         } else {
             self.toggle_timer = 0;
@@ -197,7 +508,82 @@ where
     }
 
     fn calc_offset(&self, x: usize, y: usize) -> usize {
-        y * self.bytes_per_row + x
+        if self.mode == MonitorMode::Disasm {
+            y * 4
+        } else {
+            y * self.bytes_per_row + x
+        }
+    }
+
+    fn page_size(&self) -> usize {
+        self.calc_offset(0, self.rows)
+    }
+
+    /// address of the byte under the cursor
+    pub fn cursor_addr(&self) -> usize {
+        self.addr as usize + self.calc_offset(self.cursor_x, self.cursor_y)
+    }
+
+    /**
+     * Draws one instruction per row
+     * as address, raw word and mnemonic
+     */
+    fn draw_disasm(&mut self, ctxt: &mut dyn RenderContext) {
+        let mut text = [0u8; 32];
+
+        for r in 0..self.rows {
+            let address = self.addr as usize + self.calc_offset(0, r);
+            let y = self.y + ctxt.char_height() * (r as isize + 1);
+            let word_x = self.x + 9 * ctxt.char_width();
+            let text_x = word_x + 9 * ctxt.char_width();
+            let selected = self.cursor_y == r;
+
+            ctxt.set_color(Color::new(0xFF, 0xFF, 0x00, 0xFF));
+            draw_hex(ctxt, address as u64, 8, self.x, y);
+
+            let word = match self.memory.read_u32(address) {
+                Ok(word) => word,
+                Err(_) => {
+                    if selected {
+                        ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+                    }
+                    ctxt.puts("--------", word_x, y);
+                    continue;
+                }
+            };
+            disassemble(address as u32, word, &mut text);
+
+            if selected {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            draw_hex(ctxt, word as u64, 8, word_x, y);
+
+            if selected {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            ctxt.putsu8(&text, text_x, y);
+        }
+    }
+
+    /**
+     * Draws address and current value of every byte
+     * in the page that changed within the fade window
+     */
+    fn draw_changes(&mut self, ctxt: &mut dyn RenderContext) {
+        let value_x = self.x + 9 * ctxt.char_width();
+
+        for (r, offset) in self.changes.changed_offsets().take(self.rows).enumerate() {
+            let y = self.y + ctxt.char_height() * (r as isize + 1);
+
+            ctxt.set_color(Color::new(0xFF, 0xFF, 0x00, 0xFF));
+            draw_hex(ctxt, (self.addr as usize + offset) as u64, 8, self.x, y);
+
+            let value_hex = Parser::to_hex_tuple(self.changes.value(offset).unwrap_or(0));
+            if let Some(color) = self.changes.highlight(offset) {
+                ctxt.set_color(color);
+            }
+            ctxt.cputs(&[value_hex.0 as char, value_hex.1 as char], value_x, y);
+        }
     }
 }
 
@@ -214,24 +600,43 @@ where
             return;
         }
 
+        self.changes
+            .update(&self.memory, self.addr as usize, self.page_size());
+
+        if self.number.take_result() == InputResult::Confirmed {
+            self.last_error = self
+                .write_value(self.value_addr, self.number.raw())
+                .err()
+                .map(RangeError::from);
+        }
+
         if self.keyboard.active() {
             self.keyboard.update(data);
         } else if self.keyboard.enter {
             self.keyboard.enter = false;
+            // this input cannot fail because of the restricted keyboard input
+            let input = Parser::from_hexu(&self.addr_buffer).unwrap_or(0);
             match self.keyboard.tag {
-                0 => {
-                    // this input cannot fail because of the restricted keyboard input
-                    let addr = Parser::from_hexu(&self.addr_buffer).unwrap_or(0);
-                    self.addr = addr as *mut c_void;
+                INPUT_ADDR => {
+                    self.goto(input);
                 }
-                _ => {
-                    let value = Parser::from_hexu(&self.addr_buffer).unwrap_or(0) as u8;
-                    let addr = unsafe {
-                        self.addr
-                            .add(self.calc_offset(self.cursor_x, self.cursor_y))
-                    };
-                    unsafe { *(addr as *mut u8) = value };
+                INPUT_FILL => {
+                    if let Some(selection) = self.selection() {
+                        self.last_error = match u8::try_from(input) {
+                            Ok(value) => {
+                                range::fill(&self.memory, selection, value, &mut self.undo).err()
+                            }
+                            Err(_) => Some(RangeError::BadValue),
+                        };
+                    }
                 }
+                INPUT_COPY => {
+                    if let Some(selection) = self.selection() {
+                        self.last_error =
+                            range::copy(&self.memory, selection, input, &mut self.undo).err();
+                    }
+                }
+                _ => {}
             }
         }
     }
@@ -240,6 +645,16 @@ where
         if self.keyboard.active() && self.active {
             self.keyboard.draw_buffer(ctxt, &self.addr_buffer);
             self.keyboard.draw(ctxt);
+        } else if self.number.active() && self.active {
+            draw_hex(ctxt, self.value_addr as u64, 8, self.x, self.y);
+            let value_y = self.y + ctxt.char_height();
+            self.number.draw_at(ctxt, self.x, value_y);
+        } else if self.mode == MonitorMode::Disasm {
+            draw_hex(ctxt, self.addr as u64, 8, self.x, self.y);
+            self.draw_disasm(ctxt);
+        } else if self.mode == MonitorMode::Changes {
+            draw_hex(ctxt, self.addr as u64, 8, self.x, self.y);
+            self.draw_changes(ctxt);
         } else {
             let mut stream = BufferedStream::new();
             let _ = Parser::to_hexu(&(self.addr as usize).to_be_bytes(), &mut stream); // this should not fail!
@@ -247,35 +662,50 @@ where
             ctxt.putsu8(&stream.buffer, self.x, self.y);
 
             for r in 0..self.rows {
-                let offset_hex =
-                    Parser::to_hex_tuple(unsafe { self.addr.add(self.calc_offset(0, r)) as u8 });
-
-                let addr_offset = [offset_hex.0 as char, offset_hex.1 as char];
+                // low 16 bits of the row address
+                let row_addr = self.addr as usize + self.calc_offset(0, r);
+                let hi_hex = Parser::to_hex_tuple((row_addr >> 8) as u8);
+                let lo_hex = Parser::to_hex_tuple(row_addr as u8);
+
+                let addr_offset = [
+                    hi_hex.0 as char,
+                    hi_hex.1 as char,
+                    lo_hex.0 as char,
+                    lo_hex.1 as char,
+                ];
                 let y = self.y as isize + ctxt.char_height() * (r as isize + 1);
 
                 ctxt.set_color(Color::new(0xFF, 0xFF, 0x00, 0xFF));
                 ctxt.cputs(&addr_offset, self.x as isize, y);
 
                 for c in 0..self.bytes_per_row {
-                    let address = unsafe { self.addr.add(self.calc_offset(c, r)) };
-                    let value = if address as usize >= 0x80000000 && (address as usize) < 0x807FFFFF
-                    {
-                        unsafe { *(address as *const u8) }
-                    } else {
-                        0
-                    };
+                    let address = self.addr as usize + self.calc_offset(c, r);
+
+                    let x = self.x
+                        + 2 * ctxt.char_width()
+                        + (2 * ctxt.char_width() + 4) * (c as isize + 1);
 
-                    let x = self.x as isize + (2 * ctxt.char_width() + 4) * (c as isize + 1);
+                    let selected = self
+                        .selection()
+                        .is_some_and(|selection| selection.contains(address));
 
                     if self.cursor_x == c && self.cursor_y == r {
                         ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+                    } else if selected {
+                        ctxt.set_color(Color::new(0x00, 0x80, 0xFF, 0xFF));
+                    } else if let Some(color) = self.changes.highlight(self.calc_offset(c, r)) {
+                        ctxt.set_color(color);
                     }
 
-                    let value_str = if self.ascii_mode {
-                        [ctxt.convert(value) as char, 0 as char]
-                    } else {
-                        let value_hex = Parser::to_hex_tuple(value);
-                        [value_hex.0 as char, value_hex.1 as char]
+                    let value_str = match self.memory.read_u8(address) {
+                        Ok(value) if self.mode == MonitorMode::Ascii => {
+                            [ctxt.convert(value) as char, 0 as char]
+                        }
+                        Ok(value) => {
+                            let value_hex = Parser::to_hex_tuple(value);
+                            [value_hex.0 as char, value_hex.1 as char]
+                        }
+                        Err(_) => ['-', '-'],
                     };
                     ctxt.cputs(&value_str, x, y);
                 }
@@ -295,6 +725,7 @@ where
 
         self.toggle_timer = self.toggle_timer_max;
         self.keyboard.active = false;
+        self.number.active = false;
         if self.active {
             self.close(data);
         } else {
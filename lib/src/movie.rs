@@ -0,0 +1,408 @@
+use crate::input::CONTROLLERS;
+use crate::memmap::{MemoryError, MemoryMap};
+use crate::save::{self, SaveBuffer, SaveError};
+use crate::usb::{BiError, Usb};
+use core::ptr::read_volatile;
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"UMOV";
+pub const MOVIE_VERSION: u16 = 1;
+pub const HEADER_LEN: usize = 40;
+
+/// movies are transferred in chunks of this size
+const CHUNK_LEN: usize = 512;
+
+/// crcs and game code in the cartridge header
+const ROM_CRC1: *const u32 = 0xB0000010 as *const u32;
+const ROM_CRC2: *const u32 = 0xB0000014 as *const u32;
+const ROM_CODE: *const u32 = 0xB000003C as *const u32;
+
+/// size of an OSContPad in the game's memory
+const CONT_PAD_LEN: usize = 6;
+
+pub enum MovieError {
+    /// not a movie or an unknown version
+    Invalid,
+    /// the samples do not match the checksum
+    Checksum,
+    /// the movie was recorded for a different rom
+    WrongRom,
+    /// the sample buffer is too small for the movie
+    TooLong,
+    Save(SaveError),
+    Usb(BiError),
+    Memory(MemoryError),
+}
+
+impl From<SaveError> for MovieError {
+    fn from(err: SaveError) -> Self {
+        MovieError::Save(err)
+    }
+}
+
+impl From<BiError> for MovieError {
+    fn from(err: BiError) -> Self {
+        MovieError::Usb(err)
+    }
+}
+
+impl From<MemoryError> for MovieError {
+    fn from(err: MemoryError) -> Self {
+        MovieError::Memory(err)
+    }
+}
+
+/**
+ * Identifies the rom a movie belongs to
+ * code is the cartridge id, country code and version
+ * from the cartridge header (e.g. "SME" and 0)
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub struct RomId {
+    pub crc1: u32,
+    pub crc2: u32,
+    pub code: [u8; 4],
+}
+
+impl RomId {
+    /// reads the id of the running rom
+    pub fn read() -> Self {
+        unsafe {
+            Self {
+                crc1: read_volatile(ROM_CRC1),
+                crc2: read_volatile(ROM_CRC2),
+                code: read_volatile(ROM_CODE).to_be_bytes(),
+            }
+        }
+    }
+}
+
+/**
+ * 40 bytes, big endian:
+ * "UMOV", version (u16), controllers (u8), reserved (u8),
+ * frames, rerecords, rom crc1, rom crc2, rom code,
+ * savestate, checksum of the samples, reserved (u32 each)
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub struct MovieHeader {
+    /// controllers per frame, starting at port 1
+    pub controllers: u8,
+    pub frames: u32,
+    pub rerecords: u32,
+    pub rom: RomId,
+    /// the savestate the movie starts from, 0 starts at power on
+    pub savestate: u32,
+    pub checksum: u32,
+}
+
+impl MovieHeader {
+    pub fn new(controllers: u8, rom: RomId) -> Self {
+        Self {
+            controllers: controllers.clamp(1, CONTROLLERS.len() as u8),
+            frames: 0,
+            rerecords: 0,
+            rom,
+            savestate: 0,
+            checksum: 0,
+        }
+    }
+
+    /// number of samples in the movie, None if it does not fit into memory
+    pub fn samples(&self) -> Option<usize> {
+        (self.frames as usize).checked_mul(self.controllers as usize)
+    }
+
+    pub fn encode(&self, buffer: &mut [u8]) {
+        buffer[0..4].copy_from_slice(&MOVIE_MAGIC);
+        buffer[4..6].copy_from_slice(&MOVIE_VERSION.to_be_bytes());
+        buffer[6] = self.controllers;
+        buffer[7] = 0;
+
+        let words = [
+            self.frames,
+            self.rerecords,
+            self.rom.crc1,
+            self.rom.crc2,
+            u32::from_be_bytes(self.rom.code),
+            self.savestate,
+            self.checksum,
+            0,
+        ];
+        for (word, dst) in words.iter().zip(buffer[8..HEADER_LEN].chunks_exact_mut(4)) {
+            dst.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    pub fn decode(buffer: &[u8]) -> Result<Self, MovieError> {
+        if buffer.len() < HEADER_LEN
+            || buffer[0..4] != MOVIE_MAGIC
+            || u16::from_be_bytes([buffer[4], buffer[5]]) != MOVIE_VERSION
+        {
+            return Err(MovieError::Invalid);
+        }
+
+        let controllers = buffer[6];
+        if controllers == 0 || controllers as usize > CONTROLLERS.len() {
+            return Err(MovieError::Invalid);
+        }
+
+        let word = |i: usize| {
+            let pos = 8 + i * 4;
+            u32::from_be_bytes([
+                buffer[pos],
+                buffer[pos + 1],
+                buffer[pos + 2],
+                buffer[pos + 3],
+            ])
+        };
+        let header = Self {
+            controllers,
+            frames: word(0),
+            rerecords: word(1),
+            rom: RomId {
+                crc1: word(2),
+                crc2: word(3),
+                code: word(4).to_be_bytes(),
+            },
+            savestate: word(5),
+            checksum: word(6),
+        };
+        if header.samples().is_none() {
+            return Err(MovieError::Invalid);
+        }
+        Ok(header)
+    }
+}
+
+/// checksum over the big endian bytes of the samples
+pub fn checksum(samples: &[u32]) -> u32 {
+    samples
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .fold(0u32, |sum, b| sum.rotate_left(1).wrapping_add(b as u32))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MovieState {
+    Idle,
+    Recording,
+    Playing,
+}
+
+/**
+ * Records and plays back controller input
+ * Every frame stores one sample per controller
+ * in the same format as the controller status in pif ram:
+ * buttons (u16), stick x (i8), stick y (i8)
+ */
+pub struct Movie<'a> {
+    pub header: MovieHeader,
+    samples: &'a mut [u32],
+    state: MovieState,
+    /// the next frame to record or play
+    frame: u32,
+    /// address of the game's OSContPad array that playback writes to
+    pub pads: usize,
+    pub memory: MemoryMap,
+    pub last_error: Option<MovieError>,
+}
+
+impl<'a> Movie<'a> {
+    pub fn new(samples: &'a mut [u32], pads: usize) -> Self {
+        Self {
+            header: MovieHeader::new(1, RomId::read()),
+            samples,
+            state: MovieState::Idle,
+            frame: 0,
+            pads,
+            memory: MemoryMap::detect(),
+            last_error: None,
+        }
+    }
+
+    pub fn state(&self) -> MovieState {
+        self.state
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// the most frames the sample buffer can hold
+    pub fn capacity(&self) -> u32 {
+        (self.samples.len() / self.header.controllers as usize) as u32
+    }
+
+    /// starts a new recording for the running rom
+    pub fn record(&mut self, controllers: u8) {
+        self.header = MovieHeader::new(controllers, RomId::read());
+        self.frame = 0;
+        self.state = MovieState::Recording;
+    }
+
+    pub fn play(&mut self) {
+        self.frame = 0;
+        self.state = MovieState::Playing;
+    }
+
+    /**
+     * Records again from frame, e.g. after loading a savestate of that frame
+     * Works while recording and playing or on a loaded movie,
+     * the frames after it are dropped.
+     */
+    pub fn rerecord(&mut self, frame: u32) {
+        let recorded = match self.state {
+            MovieState::Recording => self.frame,
+            _ => self.header.frames.min(self.capacity()),
+        };
+        self.frame = frame.min(recorded);
+        self.header.rerecords = self.header.rerecords.saturating_add(1);
+        self.state = MovieState::Recording;
+    }
+
+    pub fn stop(&mut self) {
+        if self.state == MovieState::Recording {
+            self.header.frames = self.frame;
+            let len = self.frame as usize * self.header.controllers as usize;
+            self.header.checksum = checksum(&self.samples[..len]);
+        }
+        self.state = MovieState::Idle;
+    }
+
+    /// the samples of the movie, the header may have been changed
+    fn recorded(&self) -> Result<&[u32], MovieError> {
+        match self.header.samples() {
+            Some(len) if len <= self.samples.len() => Ok(&self.samples[..len]),
+            _ => Err(MovieError::TooLong),
+        }
+    }
+
+    /**
+     * Call once a frame after the game read its controllers into its pads
+     * and before it uses them.
+     * Recording stops when the buffer is full, playback at the end of the movie
+     */
+    pub fn update(&mut self) {
+        let result = match self.state {
+            MovieState::Idle => return,
+            MovieState::Recording if self.frame >= self.capacity() => {
+                self.stop();
+                return;
+            }
+            MovieState::Playing if self.frame >= self.header.frames.min(self.capacity()) => {
+                self.stop();
+                return;
+            }
+            MovieState::Recording => {
+                self.capture();
+                Ok(())
+            }
+            MovieState::Playing => self.apply(),
+        };
+
+        self.frame += 1;
+        if let Err(err) = result {
+            self.last_error = Some(err);
+            self.stop();
+        }
+    }
+
+    fn capture(&mut self) {
+        let controllers = self.header.controllers as usize;
+        let start = self.frame as usize * controllers;
+        for (sample, controller) in self.samples[start..start + controllers]
+            .iter_mut()
+            .zip(CONTROLLERS.iter())
+        {
+            *sample = unsafe { read_volatile(*controller) };
+        }
+    }
+
+    fn apply(&self) -> Result<(), MovieError> {
+        let controllers = self.header.controllers as usize;
+        let start = self.frame as usize * controllers;
+        let samples = &self.samples[start..start + controllers];
+
+        for (port, sample) in samples.iter().enumerate() {
+            let pad = self.pads + port * CONT_PAD_LEN;
+            let bytes = sample.to_be_bytes();
+            self.memory.write_u16(pad, (*sample >> 16) as u16)?;
+            self.memory.write_u8(pad + 2, bytes[2])?;
+            self.memory.write_u8(pad + 3, bytes[3])?;
+        }
+        Ok(())
+    }
+
+    /// header followed by the samples in chunks
+    fn save_with<F>(&mut self, mut write: F) -> Result<(), MovieError>
+    where
+        F: FnMut(usize, &mut [u8]) -> Result<(), MovieError>,
+    {
+        let mut chunk = SaveBuffer::<CHUNK_LEN>::new();
+        self.header.encode(&mut chunk.data);
+        write(0, &mut chunk.data[..HEADER_LEN])?;
+
+        let mut offset = HEADER_LEN;
+        for samples in self.recorded()?.chunks(CHUNK_LEN / 4) {
+            for (sample, dst) in samples.iter().zip(chunk.data.chunks_exact_mut(4)) {
+                dst.copy_from_slice(&sample.to_be_bytes());
+            }
+            let len = samples.len() * 4;
+            write(offset, &mut chunk.data[..len])?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    fn load_with<F>(&mut self, mut read: F) -> Result<(), MovieError>
+    where
+        F: FnMut(usize, &mut [u8]) -> Result<(), MovieError>,
+    {
+        let mut chunk = SaveBuffer::<CHUNK_LEN>::new();
+        read(0, &mut chunk.data[..HEADER_LEN])?;
+        let header = MovieHeader::decode(&chunk.data)?;
+        if header.rom != RomId::read() {
+            return Err(MovieError::WrongRom);
+        }
+        let len = header.samples().ok_or(MovieError::Invalid)?;
+        if len > self.samples.len() {
+            return Err(MovieError::TooLong);
+        }
+
+        let mut offset = HEADER_LEN;
+        for samples in self.samples[..len].chunks_mut(CHUNK_LEN / 4) {
+            let len = samples.len() * 4;
+            read(offset, &mut chunk.data[..len])?;
+            for (sample, src) in samples.iter_mut().zip(chunk.data.chunks_exact(4)) {
+                *sample = u32::from_be_bytes([src[0], src[1], src[2], src[3]]);
+            }
+            offset += len;
+        }
+
+        if checksum(&self.samples[..len]) != header.checksum {
+            return Err(MovieError::Checksum);
+        }
+        self.header = header;
+        self.state = MovieState::Idle;
+        self.frame = 0;
+        Ok(())
+    }
+
+    /// sends the movie to the host
+    pub fn save_usb(&mut self) -> Result<(), MovieError> {
+        self.save_with(|_, data| Ok(Usb::write(data)?))
+    }
+
+    /// receives a movie from the host, the host has to send it right away
+    pub fn load_usb(&mut self, usb: &Usb) -> Result<(), MovieError> {
+        self.load_with(|_, data| Ok(usb.read_usb(data)?))
+    }
+
+    /// stores the movie in sram starting at offset
+    pub fn save_sram(&mut self, offset: usize) -> Result<(), MovieError> {
+        self.save_with(|pos, data| Ok(save::write(offset + pos, data)?))
+    }
+
+    pub fn load_sram(&mut self, offset: usize) -> Result<(), MovieError> {
+        self.load_with(|pos, data| Ok(save::read(offset + pos, data)?))
+    }
+}
@@ -0,0 +1,350 @@
+use core::marker::PhantomData;
+
+use crate::color::Color;
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::textinput::InputResult;
+
+/// the most digits any width can need (u32 in decimal)
+const MAX_DIGITS: usize = 10;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Base {
+    Dec = 10,
+    Hex = 16,
+}
+
+/// width of the value in bytes
+#[derive(Copy, Clone, PartialEq)]
+pub enum Width {
+    U8 = 1,
+    U16 = 2,
+    U32 = 4,
+}
+
+impl Width {
+    pub fn bits(self) -> u32 {
+        self as u32 * 8
+    }
+
+    pub fn mask(self) -> u32 {
+        u32::MAX >> (32 - self.bits())
+    }
+
+    /// smallest and largest value that fits
+    pub fn limits(self, signed: bool) -> (i64, i64) {
+        if signed {
+            let half = 1i64 << (self.bits() - 1);
+            (-half, half - 1)
+        } else {
+            (0, self.mask() as i64)
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::U8 => Self::U16,
+            Self::U16 => Self::U32,
+            Self::U32 => Self::U8,
+        }
+    }
+}
+
+/**
+ * Edits a number by scrolling
+ * each digit up or down.
+ * Left and right select the digit,
+ * signed inputs have an extra sign position
+ * in front of the most significant digit.
+ * The value is always kept inside min and max.
+ */
+#[derive(Copy, Clone)]
+pub struct NumberInput<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    value: i64,
+    /// the value given to open, restored on cancel
+    original: i64,
+    base: Base,
+    width: Width,
+    signed: bool,
+    min: i64,
+    max: i64,
+    digits: usize,
+    /// 0 is the least significant digit
+    cursor: usize,
+    result: InputResult,
+    phantom: PhantomData<T>,
+}
+
+impl<T> NumberInput<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, base: Base, width: Width, signed: bool) -> Self {
+        let (min, max) = width.limits(signed);
+        let mut input = Self {
+            x,
+            y,
+            active: false,
+            value: 0,
+            original: 0,
+            base,
+            width,
+            signed,
+            min,
+            max,
+            digits: 0,
+            cursor: 0,
+            result: InputResult::Pending,
+            phantom: PhantomData,
+        };
+        input.digits = input.calc_digits();
+        input
+    }
+
+    /// restricts the range further, the range never exceeds the width
+    pub fn with_range(mut self, min: i64, max: i64) -> Self {
+        self.set_range(min, max);
+        self
+    }
+
+    pub fn set_range(&mut self, min: i64, max: i64) {
+        let (lower, upper) = self.width.limits(self.signed);
+        self.min = i64::max(lower, i64::min(min, max));
+        self.max = i64::min(upper, i64::max(min, max));
+        self.digits = self.calc_digits();
+        self.cursor = usize::min(self.cursor, self.last_position());
+        self.value = self.value.clamp(self.min, self.max);
+    }
+
+    /// changes the width and resets the range to the new width's limits
+    pub fn set_width(&mut self, width: Width) {
+        self.width = width;
+        let (min, max) = width.limits(self.signed);
+        self.set_range(min, max);
+    }
+
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    /// the number of digits needed to show the largest possible value
+    fn calc_digits(&self) -> usize {
+        let mut largest = u64::max(self.min.unsigned_abs(), self.max.unsigned_abs());
+        let mut digits = 1;
+        while largest >= self.base as u64 {
+            largest /= self.base as u64;
+            digits += 1;
+        }
+        usize::min(digits, MAX_DIGITS)
+    }
+
+    fn last_position(&self) -> usize {
+        if self.signed {
+            self.digits
+        } else {
+            self.digits - 1
+        }
+    }
+
+    fn is_sign_position(&self) -> bool {
+        self.signed && self.cursor == self.digits
+    }
+
+    /**
+     * Starts editing value
+     * the cursor starts at the least significant digit
+     */
+    pub fn open(&mut self, value: i64) {
+        self.value = value.clamp(self.min, self.max);
+        self.original = self.value;
+        self.cursor = 0;
+        self.result = InputResult::Pending;
+        self.active = true;
+    }
+
+    /// starts editing a value read from memory in the input's width
+    pub fn open_raw(&mut self, raw: u32) {
+        let raw = raw & self.width.mask();
+        let value = if self.signed {
+            let shift = 32 - self.width.bits();
+            ((raw << shift) as i32 >> shift) as i64
+        } else {
+            raw as i64
+        };
+        self.open(value);
+    }
+
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// the value truncated to the input's width, ready to be written
+    pub fn raw(&self) -> u32 {
+        self.value as u32 & self.width.mask()
+    }
+
+    /**
+     * Returns the result once and resets it
+     * to pending afterwards
+     */
+    pub fn take_result(&mut self) -> InputResult {
+        let result = self.result;
+        self.result = InputResult::Pending;
+        result
+    }
+
+    fn digit(&self, position: usize) -> u64 {
+        let base = self.base as u64;
+        (self.value.unsigned_abs() / base.pow(position as u32)) % base
+    }
+
+    /**
+     * Replaces the digit under the cursor
+     * the other digits are not affected
+     */
+    fn set_digit(&mut self, digit: u64) {
+        let place = (self.base as u64).pow(self.cursor as u32);
+        let magnitude = self.value.unsigned_abs() - self.digit(self.cursor) * place + digit * place;
+        let magnitude = i64::try_from(magnitude).unwrap_or(i64::MAX);
+        self.value = if self.value < 0 {
+            -magnitude
+        } else {
+            magnitude
+        };
+        self.value = self.value.clamp(self.min, self.max);
+    }
+
+    pub fn up(&mut self) {
+        if self.is_sign_position() {
+            self.value = self.value.saturating_neg().clamp(self.min, self.max);
+        } else {
+            let base = self.base as u64;
+            self.set_digit((self.digit(self.cursor) + 1) % base);
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.is_sign_position() {
+            self.value = self.value.saturating_neg().clamp(self.min, self.max);
+        } else {
+            let base = self.base as u64;
+            self.set_digit((self.digit(self.cursor) + base - 1) % base);
+        }
+    }
+
+    /// moves to the more significant digit
+    pub fn left(&mut self) {
+        if self.cursor < self.last_position() {
+            self.cursor += 1;
+        } else {
+            self.cursor = 0;
+        }
+    }
+
+    /// moves to the less significant digit
+    pub fn right(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        } else {
+            self.cursor = self.last_position();
+        }
+    }
+
+    pub fn confirm(&mut self) {
+        self.result = InputResult::Confirmed;
+        self.active = false;
+    }
+
+    /// drops the edits and goes back to the value given to open
+    pub fn cancel(&mut self) {
+        self.value = self.original;
+        self.result = InputResult::Cancelled;
+        self.active = false;
+    }
+
+    /**
+     * Draws the digits at x and y
+     * the digit under the cursor is only highlighted
+     * while the input is active
+     */
+    pub fn draw_at(&self, ctxt: &mut dyn RenderContext, x: isize, y: isize) {
+        let mut column = 0;
+        if self.signed {
+            let sign = if self.value < 0 { b'-' } else { b'+' };
+            if self.active && self.is_sign_position() {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            ctxt.putsu8(&[sign, 0], x, y);
+            column += 1;
+        }
+
+        for position in (0..self.digits).rev() {
+            let digit = self.digit(position) as u8;
+            let c = if digit < 10 {
+                b'0' + digit
+            } else {
+                b'A' + digit - 10
+            };
+
+            if self.active && position == self.cursor {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            ctxt.putsu8(&[c, 0], x + column * ctxt.char_width(), y);
+            column += 1;
+        }
+    }
+}
+
+impl<T> Drawable<T> for NumberInput<T>
+where
+    T: Copy + Clone,
+{
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if self.active {
+            self.draw_at(ctxt, self.x, self.y);
+        }
+    }
+
+    fn update(&mut self, _data: T) {}
+}
+
+impl<T> Widget<T> for NumberInput<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        if self.active {
+            self.cancel();
+        } else {
+            self.open(self.value);
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_restores_the_opened_value() {
+        let mut input: NumberInput<()> = NumberInput::new(0, 0, Base::Dec, Width::U8, false);
+        input.open(42);
+        input.up();
+        input.left();
+        input.up();
+        assert_eq!(input.value(), 53);
+
+        input.cancel();
+        assert_eq!(input.value(), 42);
+        assert!(!input.active());
+    }
+}
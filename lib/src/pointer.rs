@@ -0,0 +1,375 @@
+use crate::memmap::{MemoryAccess, MemoryError};
+use crate::text::TextWriter;
+
+/// the most offsets and dereferences a chain can hold
+pub const MAX_CHAIN_OPS: usize = 8;
+
+/// the longest expression text that is accepted
+pub const MAX_EXPR_LEN: usize = 64;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ParseError {
+    /// unexpected character at this position
+    Syntax(usize),
+    TooLong,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ChainError {
+    /// a pointer in the chain was 0
+    Null,
+    Memory(MemoryError),
+}
+
+impl From<MemoryError> for ChainError {
+    fn from(err: MemoryError) -> Self {
+        ChainError::Memory(err)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ChainOp {
+    /// replaces the address with the word it points to
+    Deref,
+    Add(i32),
+}
+
+/**
+ * A base address followed by offsets and dereferences
+ * e.g. [[0x8033B170]+0x40]+0x0C reads the word at 0x8033B170,
+ * adds 0x40, reads the word at that address and adds 0x0C.
+ * Numbers are always hex, the 0x prefix is optional.
+ */
+#[derive(Copy, Clone)]
+pub struct PointerChain {
+    base: u32,
+    ops: [ChainOp; MAX_CHAIN_OPS],
+    len: usize,
+}
+
+impl PointerChain {
+    pub fn new(base: u32) -> Self {
+        Self {
+            base,
+            ops: [ChainOp::Add(0); MAX_CHAIN_OPS],
+            len: 0,
+        }
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    pub fn ops(&self) -> &[ChainOp] {
+        &self.ops[..self.len]
+    }
+
+    pub fn push(&mut self, op: ChainOp) -> Result<(), ParseError> {
+        if self.len >= MAX_CHAIN_OPS {
+            return Err(ParseError::TooLong);
+        }
+        self.ops[self.len] = op;
+        self.len += 1;
+        Ok(())
+    }
+
+    /**
+     * Parses an expression
+     * expr := term (('+' | '-') number)*
+     * term := number | '[' expr ']'
+     * Spaces are ignored and the text may be null terminated
+     */
+    pub fn parse(text: &[u8]) -> Result<Self, ParseError> {
+        let len = text.iter().position(|c| *c == 0).unwrap_or(text.len());
+        if len > MAX_EXPR_LEN {
+            return Err(ParseError::TooLong);
+        }
+
+        let mut parser = Parser {
+            text: &text[..len],
+            pos: 0,
+            chain: Self::new(0),
+        };
+        parser.expr()?;
+        parser.skip_spaces();
+        if parser.pos != len {
+            return Err(ParseError::Syntax(parser.pos));
+        }
+        Ok(parser.chain)
+    }
+
+    /// can be used as a text input validator
+    pub fn is_valid(text: &[u8]) -> bool {
+        Self::parse(text).is_ok()
+    }
+
+    /// can be used as a text input character filter
+    pub fn is_expr_char(c: u8) -> bool {
+        c.is_ascii_hexdigit() || matches!(c, b'[' | b']' | b'+' | b'-' | b'x' | b'X' | b' ')
+    }
+
+    /**
+     * Follows the chain and returns the final address
+     * every pointer that is read has to be non-null,
+     * mapped and aligned
+     */
+    pub fn eval<M: MemoryAccess>(&self, memory: &M) -> Result<usize, ChainError> {
+        let mut addr = self.base;
+        for op in self.ops() {
+            match op {
+                ChainOp::Deref => {
+                    if addr == 0 {
+                        return Err(ChainError::Null);
+                    }
+                    addr = memory.read_u32(addr as usize)?;
+                    // offsets are not applied to null pointers
+                    if addr == 0 {
+                        return Err(ChainError::Null);
+                    }
+                }
+                ChainOp::Add(offset) => addr = addr.wrapping_add(*offset as u32),
+            }
+        }
+
+        if addr == 0 {
+            Err(ChainError::Null)
+        } else {
+            Ok(addr as usize)
+        }
+    }
+
+    /// writes the chain back in the same syntax parse accepts
+    pub fn write(&self, w: &mut TextWriter) {
+        let depth = self
+            .ops()
+            .iter()
+            .filter(|op| **op == ChainOp::Deref)
+            .count();
+        for _ in 0..depth {
+            w.push(b'[');
+        }
+
+        w.puts("0x");
+        w.hex(self.base as u64, 8);
+        for op in self.ops() {
+            match op {
+                ChainOp::Deref => w.push(b']'),
+                ChainOp::Add(offset) => {
+                    w.puts(if *offset < 0 { "-0x" } else { "+0x" });
+                    let offset = offset.unsigned_abs();
+                    let mut digits = 1;
+                    while digits < 8 && (offset >> (digits * 4)) != 0 {
+                        digits += 1;
+                    }
+                    w.hex(offset as u64, digits);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Recursive descent parser that flattens
+ * the expression into the chain while parsing
+ */
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    chain: PointerChain,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, ParseError> {
+        self.skip_spaces();
+        if self.peek() == Some(b'0')
+            && matches!(self.text.get(self.pos + 1), Some(b'x') | Some(b'X'))
+        {
+            self.pos += 2;
+        }
+
+        let start = self.pos;
+        let mut value: u32 = 0;
+        while let Some(c) = self.peek() {
+            let digit = match c {
+                b'0'..=b'9' => c - b'0',
+                b'a'..=b'f' => c - b'a' + 10,
+                b'A'..=b'F' => c - b'A' + 10,
+                _ => break,
+            };
+
+            if self.pos - start >= 8 {
+                return Err(ParseError::Syntax(self.pos));
+            }
+            value = (value << 4) | digit as u32;
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            Err(ParseError::Syntax(self.pos))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn term(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        if self.peek() == Some(b'[') {
+            self.pos += 1;
+            self.expr()?;
+            self.skip_spaces();
+            if self.peek() != Some(b']') {
+                return Err(ParseError::Syntax(self.pos));
+            }
+            self.pos += 1;
+            self.chain.push(ChainOp::Deref)
+        } else {
+            self.chain.base = self.number()?;
+            Ok(())
+        }
+    }
+
+    fn expr(&mut self) -> Result<(), ParseError> {
+        self.term()?;
+        loop {
+            self.skip_spaces();
+            let negative = match self.peek() {
+                Some(b'+') => false,
+                Some(b'-') => true,
+                _ => return Ok(()),
+            };
+            self.pos += 1;
+
+            let offset = self.number()? as i32;
+            self.chain.push(ChainOp::Add(if negative {
+                offset.wrapping_neg()
+            } else {
+                offset
+            }))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memmap::{BufferMemory, KSEG0};
+
+    fn parse(text: &str) -> PointerChain {
+        PointerChain::parse(text.as_bytes()).ok().unwrap()
+    }
+
+    fn error(text: &str) -> Option<ParseError> {
+        PointerChain::parse(text.as_bytes()).err()
+    }
+
+    fn text(chain: &PointerChain) -> String {
+        let mut buffer = [0u8; 2 * MAX_EXPR_LEN];
+        let mut w = TextWriter::new(&mut buffer);
+        chain.write(&mut w);
+        let len = w.finish();
+        String::from_utf8(buffer[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn parses_the_example() {
+        let chain = parse("[[0x8033B170]+0x40]+0x0C");
+        assert_eq!(chain.base(), 0x8033B170);
+        assert!(
+            chain.ops()
+                == [
+                    ChainOp::Deref,
+                    ChainOp::Add(0x40),
+                    ChainOp::Deref,
+                    ChainOp::Add(0x0C)
+                ]
+        );
+    }
+
+    #[test]
+    fn nesting_and_negative_offsets() {
+        let chain = parse("[[[8033b170]+40]+C]-10");
+        assert!(
+            chain.ops()
+                == [
+                    ChainOp::Deref,
+                    ChainOp::Add(0x40),
+                    ChainOp::Deref,
+                    ChainOp::Add(0x0C),
+                    ChainOp::Deref,
+                    ChainOp::Add(-0x10)
+                ]
+        );
+
+        let chain = parse(" [ 0X80001000 ] - 4 + 8\0ignored");
+        assert_eq!(chain.base(), 0x80001000);
+        assert!(chain.ops() == [ChainOp::Deref, ChainOp::Add(-4), ChainOp::Add(8)]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(error("FFFFFFFF").is_none());
+        assert!(error("123456789") == Some(ParseError::Syntax(8)));
+        assert!(error("") == Some(ParseError::Syntax(0)));
+        assert!(error("[80001000") == Some(ParseError::Syntax(9)));
+        assert!(error("80001000]") == Some(ParseError::Syntax(8)));
+        assert!(error("80001000+") == Some(ParseError::Syntax(9)));
+        assert!(error("0x") == Some(ParseError::Syntax(2)));
+        assert!(error("8000g000") == Some(ParseError::Syntax(4)));
+    }
+
+    #[test]
+    fn length_limits() {
+        let long = format!("{}0", " ".repeat(MAX_EXPR_LEN));
+        assert!(error(&long) == Some(ParseError::TooLong));
+        assert!(error(&long[1..]).is_none());
+        assert!(error("0+1+1+1+1+1+1+1+1").is_none());
+        assert!(error("0+1+1+1+1+1+1+1+1+1") == Some(ParseError::TooLong));
+    }
+
+    #[test]
+    fn round_trips() {
+        for expr in [
+            "80001000",
+            "[[0x8033B170]+0x40]+0x0C",
+            "[[[8033B170]+40]+C]-10",
+            "[80000000-7FFFFFFF]+FFFFFFFF",
+        ] {
+            let chain = parse(expr);
+            let written = text(&chain);
+            let again = parse(&written);
+            assert_eq!(again.base(), chain.base(), "{}", written);
+            assert!(again.ops() == chain.ops(), "{}", written);
+        }
+        assert_eq!(
+            text(&parse("[[0x8033B170]+0x40]-0x0C")),
+            "[[0x8033B170]+0x40]-0xC"
+        );
+    }
+
+    #[test]
+    fn eval() {
+        let mut rdram = [0u8; 0x100];
+        rdram[0x10..0x14].copy_from_slice(&0x80000040u32.to_be_bytes());
+        rdram[0x48..0x4C].copy_from_slice(&0x80000080u32.to_be_bytes());
+        let memory = BufferMemory::new(KSEG0, &mut rdram);
+        let eval = |expr: &str| parse(expr).eval(&memory);
+
+        assert!(eval("[[80000010]+8]+C") == Ok(0x8000008C));
+        assert!(eval("80000010-10") == Ok(0x80000000));
+        assert!(eval("[80000020]+4") == Err(ChainError::Null));
+        assert!(eval("[0]") == Err(ChainError::Null));
+        assert!(eval("4-4") == Err(ChainError::Null));
+        assert!(eval("[80001000]") == Err(ChainError::Memory(MemoryError::Unmapped)));
+        assert!(eval("[80000011]") == Err(ChainError::Memory(MemoryError::Unaligned)));
+    }
+}
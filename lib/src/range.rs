@@ -0,0 +1,190 @@
+use crate::memmap::{MemoryError, MemoryMap};
+use crate::save::SaveBuffer;
+use crate::usb::{BiError, Usb};
+
+/// largest range that can be modified so it can always be undone
+pub const UNDO_LEN: usize = 512;
+
+/// size of a single usb transfer when dumping
+const DUMP_CHUNK: usize = 512;
+const DUMP_MAGIC: [u8; 4] = *b"DUMP";
+
+pub enum RangeError {
+    TooLarge,
+    /// the fill value does not fit into a byte
+    BadValue,
+    Memory(MemoryError),
+    Usb(BiError),
+}
+
+impl From<MemoryError> for RangeError {
+    fn from(err: MemoryError) -> Self {
+        RangeError::Memory(err)
+    }
+}
+
+impl From<BiError> for RangeError {
+    fn from(err: BiError) -> Self {
+        RangeError::Usb(err)
+    }
+}
+
+/**
+ * An inclusive address range
+ */
+#[derive(Copy, Clone)]
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Range {
+    /// creates a range from two corners in any order
+    pub fn new(a: usize, b: usize) -> Self {
+        Self {
+            start: usize::min(a, b),
+            end: usize::max(a, b),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr <= self.end
+    }
+}
+
+/**
+ * Holds the original bytes of the last
+ * range operation
+ */
+pub struct UndoBuffer {
+    addr: usize,
+    len: usize,
+    data: [u8; UNDO_LEN],
+}
+
+impl UndoBuffer {
+    pub fn new() -> Self {
+        Self {
+            addr: 0,
+            len: 0,
+            data: [0; UNDO_LEN],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// saves len bytes at addr
+    fn save(&mut self, memory: &MemoryMap, addr: usize, len: usize) -> Result<(), RangeError> {
+        if len > UNDO_LEN {
+            return Err(RangeError::TooLarge);
+        }
+
+        for i in 0..len {
+            self.data[i] = memory.read_u8(addr + i)?;
+        }
+        self.addr = addr;
+        self.len = len;
+        Ok(())
+    }
+
+    /// writes the saved bytes back and empties the buffer
+    pub fn restore(&mut self, memory: &MemoryMap) -> Result<(), RangeError> {
+        for i in 0..self.len {
+            memory.write_u8(self.addr + i, self.data[i])?;
+        }
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl Default for UndoBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Sets every byte in range to value
+ */
+pub fn fill(
+    memory: &MemoryMap,
+    range: Range,
+    value: u8,
+    undo: &mut UndoBuffer,
+) -> Result<(), RangeError> {
+    if !memory.can_write(range.start, range.size()) {
+        return Err(RangeError::Memory(MemoryError::Denied));
+    }
+    undo.save(memory, range.start, range.size())?;
+
+    for addr in range.start..=range.end {
+        memory.write_u8(addr, value)?;
+    }
+    Ok(())
+}
+
+/**
+ * Copies range to dest
+ * overlapping ranges are handled like memmove
+ */
+pub fn copy(
+    memory: &MemoryMap,
+    range: Range,
+    dest: usize,
+    undo: &mut UndoBuffer,
+) -> Result<(), RangeError> {
+    let len = range.size();
+    if !memory.can_read(range.start, len) || !memory.can_write(dest, len) {
+        return Err(RangeError::Memory(MemoryError::Denied));
+    }
+    undo.save(memory, dest, len)?;
+
+    if dest > range.start {
+        for i in (0..len).rev() {
+            memory.write_u8(dest + i, memory.read_u8(range.start + i)?)?;
+        }
+    } else {
+        for i in 0..len {
+            memory.write_u8(dest + i, memory.read_u8(range.start + i)?)?;
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Sends range to the host over usb
+ * The transfer starts with a 16 byte header:
+ * "DUMP", start address and length (big endian) and 4 bytes of padding
+ * followed by the data in chunks of up to 512 bytes
+ */
+pub fn dump(memory: &MemoryMap, range: Range) -> Result<(), RangeError> {
+    let len = range.size();
+    if !memory.can_read(range.start, len) {
+        return Err(RangeError::Memory(MemoryError::Denied));
+    }
+
+    // usb dma needs 8 byte aligned buffers
+    let mut header = SaveBuffer::<16>::new();
+    header.data[0..4].copy_from_slice(&DUMP_MAGIC);
+    header.data[4..8].copy_from_slice(&(range.start as u32).to_be_bytes());
+    header.data[8..12].copy_from_slice(&(len as u32).to_be_bytes());
+    Usb::write(&mut header.data)?;
+
+    let mut buffer = SaveBuffer::<DUMP_CHUNK>::new();
+    let mut offset = 0;
+    while offset < len {
+        let chunk = usize::min(DUMP_CHUNK, len - offset);
+        for (i, b) in buffer.data[..chunk].iter_mut().enumerate() {
+            *b = memory.read_u8(range.start + offset + i)?;
+        }
+        Usb::write(&mut buffer.data[..chunk])?;
+        offset += chunk;
+    }
+    Ok(())
+}
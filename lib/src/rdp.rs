@@ -1,3 +1,4 @@
+use super::color::Color;
 use super::font::*;
 use super::interrupt::{DisableIntFn, EnableIntFn};
 use super::memory::umemset;
@@ -277,6 +278,22 @@ impl RenderContext for RdpFontRendererContext<'_> {
         }
     }
 
+    fn fill_rect(&mut self, x: isize, y: isize, w: isize, h: isize, color: Color) -> bool {
+        // the fill color holds two pixels
+        let packed = color.to_rgba5551() as u32;
+        unsafe {
+            self.draw_primitives();
+            self.draw_rect(
+                (packed << 16) | packed,
+                x as i32,
+                y as i32,
+                (x + w) as i32,
+                (y + h) as i32,
+            );
+        }
+        true
+    }
+
     fn putsu8(&mut self, s: &[u8], x: isize, y: isize) {
         let mut current_x = x;
         unsafe {
@@ -35,6 +35,13 @@ pub trait RenderContext {
         false
     }
 
+    /// optional method, can be ignored
+    /// if not supported
+    /// draws a filled rectangle, return false whenever it is not supported
+    fn fill_rect(&mut self, _x: isize, _y: isize, _w: isize, _h: isize, _color: Color) -> bool {
+        false
+    }
+
     fn char_width(&self) -> isize {
         10
     }
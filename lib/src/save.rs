@@ -0,0 +1,99 @@
+use crate::usb::{read_sram, write_sram};
+
+/// 256kbit sram, the most common sram size
+pub const SRAM_SIZE: usize = 0x8000;
+
+/// magic and checksum in front of every block
+pub const BLOCK_HEADER_LEN: usize = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SaveError {
+    OutOfRange,
+    /// dma needs 8 byte aligned buffers and even lengths
+    Unaligned,
+    /// the magic or checksum did not match
+    Invalid,
+}
+
+/**
+ * A buffer that can be used for sram dma
+ */
+#[repr(align(8))]
+pub struct SaveBuffer<const N: usize> {
+    pub data: [u8; N],
+}
+
+impl<const N: usize> SaveBuffer<N> {
+    pub fn new() -> Self {
+        Self { data: [0; N] }
+    }
+}
+
+impl<const N: usize> Default for SaveBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_access(offset: usize, data: &[u8]) -> Result<(), SaveError> {
+    if data.is_empty() || offset + data.len() > SRAM_SIZE {
+        Err(SaveError::OutOfRange)
+    } else if offset % 2 != 0 || data.len() % 2 != 0 || data.as_ptr() as usize % 8 != 0 {
+        Err(SaveError::Unaligned)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn read(offset: usize, data: &mut [u8]) -> Result<(), SaveError> {
+    check_access(offset, data)?;
+    read_sram(offset, data);
+    Ok(())
+}
+
+pub fn write(offset: usize, data: &mut [u8]) -> Result<(), SaveError> {
+    check_access(offset, data)?;
+    write_sram(offset, data);
+    Ok(())
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0u32, |sum, b| sum.rotate_left(1).wrapping_add(*b as u32))
+}
+
+/**
+ * Writes the magic and a checksum of the payload
+ * into the first BLOCK_HEADER_LEN bytes of block
+ */
+pub fn seal(magic: [u8; 4], block: &mut [u8]) {
+    let sum = checksum(&block[BLOCK_HEADER_LEN..]);
+    block[0..4].copy_from_slice(&magic);
+    block[4..8].copy_from_slice(&sum.to_be_bytes());
+}
+
+/// checks a block that was sealed with the same magic
+pub fn verify(magic: [u8; 4], block: &[u8]) -> Result<(), SaveError> {
+    if block.len() < BLOCK_HEADER_LEN || block[0..4] != magic {
+        return Err(SaveError::Invalid);
+    }
+
+    let sum = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+    if sum == checksum(&block[BLOCK_HEADER_LEN..]) {
+        Ok(())
+    } else {
+        Err(SaveError::Invalid)
+    }
+}
+
+/// reads and verifies a block
+pub fn load(offset: usize, magic: [u8; 4], block: &mut [u8]) -> Result<(), SaveError> {
+    read(offset, block)?;
+    verify(magic, block)
+}
+
+/// seals and writes a block
+pub fn store(offset: usize, magic: [u8; 4], block: &mut [u8]) -> Result<(), SaveError> {
+    seal(magic, block);
+    write(offset, block)
+}
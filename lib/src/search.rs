@@ -0,0 +1,592 @@
+use crate::color::Color;
+use crate::keyboard::{Keyboard, HEX};
+use crate::memmap::{MemoryAccess, MemoryError, MemoryMap, KSEG0, PHYS_MASK};
+use crate::menu::EntryTypes;
+use crate::monitor::Monitor;
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::text::draw_hex;
+use crate::watch::WatchType;
+use embedgdb::Parser;
+
+/**
+ * RAM search (cheat finder)
+ * Candidates are stored as a bitmap with one bit per element
+ * and the last seen value of every element is kept in a snapshot buffer.
+ * Both buffers are provided by the caller which sets the memory budget.
+ * The searched range is clamped to what the buffers can hold.
+ */
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SearchWidth {
+    U8 = 1,
+    U16 = 2,
+    U32 = 4,
+}
+
+impl SearchWidth {
+    pub fn bytes(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SearchWidth::U8 => SearchWidth::U16,
+            SearchWidth::U16 => SearchWidth::U32,
+            SearchWidth::U32 => SearchWidth::U8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Filter {
+    Equal(u32),
+    NotEqual(u32),
+    Greater(u32),
+    Less(u32),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    IncreasedBy(u32),
+    DecreasedBy(u32),
+}
+
+impl Filter {
+    /**
+     * All filters in the order the finder cycles through them
+     * value carrying filters use 0 as a placeholder
+     */
+    pub const ALL: [Filter; 10] = [
+        Filter::Equal(0),
+        Filter::NotEqual(0),
+        Filter::Greater(0),
+        Filter::Less(0),
+        Filter::Changed,
+        Filter::Unchanged,
+        Filter::Increased,
+        Filter::Decreased,
+        Filter::IncreasedBy(0),
+        Filter::DecreasedBy(0),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Equal(_) => "==",
+            Filter::NotEqual(_) => "!=",
+            Filter::Greater(_) => ">",
+            Filter::Less(_) => "<",
+            Filter::Changed => "changed",
+            Filter::Unchanged => "unchanged",
+            Filter::Increased => "increased",
+            Filter::Decreased => "decreased",
+            Filter::IncreasedBy(_) => "inc by",
+            Filter::DecreasedBy(_) => "dec by",
+        }
+    }
+
+    pub fn needs_value(&self) -> bool {
+        matches!(
+            self,
+            Filter::Equal(_)
+                | Filter::NotEqual(_)
+                | Filter::Greater(_)
+                | Filter::Less(_)
+                | Filter::IncreasedBy(_)
+                | Filter::DecreasedBy(_)
+        )
+    }
+
+    /// returns the same filter with a new value
+    pub fn with_value(&self, value: u32) -> Self {
+        match self {
+            Filter::Equal(_) => Filter::Equal(value),
+            Filter::NotEqual(_) => Filter::NotEqual(value),
+            Filter::Greater(_) => Filter::Greater(value),
+            Filter::Less(_) => Filter::Less(value),
+            Filter::IncreasedBy(_) => Filter::IncreasedBy(value),
+            Filter::DecreasedBy(_) => Filter::DecreasedBy(value),
+            _ => *self,
+        }
+    }
+
+    pub fn matches(&self, previous: u32, current: u32) -> bool {
+        match *self {
+            Filter::Equal(v) => current == v,
+            Filter::NotEqual(v) => current != v,
+            Filter::Greater(v) => current > v,
+            Filter::Less(v) => current < v,
+            Filter::Changed => current != previous,
+            Filter::Unchanged => current == previous,
+            Filter::Increased => current > previous,
+            Filter::Decreased => current < previous,
+            Filter::IncreasedBy(v) => current == previous.wrapping_add(v),
+            Filter::DecreasedBy(v) => current == previous.wrapping_sub(v),
+        }
+    }
+}
+
+pub struct RamSearch<'a> {
+    start: usize,
+    len: usize,
+    width: SearchWidth,
+    bitmap: &'a mut [u8],
+    snapshot: &'a mut [u8],
+    candidates: usize,
+}
+
+impl<'a> RamSearch<'a> {
+    pub fn new(bitmap: &'a mut [u8], snapshot: &'a mut [u8]) -> Self {
+        Self {
+            start: KSEG0,
+            len: 0,
+            width: SearchWidth::U8,
+            bitmap,
+            snapshot,
+            candidates: 0,
+        }
+    }
+
+    /// number of bytes a search can cover
+    pub fn capacity(&self, width: SearchWidth) -> usize {
+        usize::min(
+            self.bitmap.len() * 8 * width.bytes(),
+            self.snapshot.len() / width.bytes() * width.bytes(),
+        )
+    }
+
+    pub fn width(&self) -> SearchWidth {
+        self.width
+    }
+
+    /// first address of the searched range
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    fn elements(&self) -> usize {
+        self.len / self.width.bytes()
+    }
+
+    fn is_candidate(&self, i: usize) -> bool {
+        self.bitmap[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    fn clear_candidate(&mut self, i: usize) {
+        self.bitmap[i / 8] &= !(1 << (i % 8));
+        self.candidates -= 1;
+    }
+
+    fn addr(&self, i: usize) -> usize {
+        self.start + i * self.width.bytes()
+    }
+
+    fn read<M: MemoryAccess>(&self, memory: &M, addr: usize) -> Result<u32, MemoryError> {
+        match self.width {
+            SearchWidth::U8 => memory.read_u8(addr).map(|v| v as u32),
+            SearchWidth::U16 => memory.read_u16(addr).map(|v| v as u32),
+            SearchWidth::U32 => memory.read_u32(addr),
+        }
+    }
+
+    /// value of element i as of the last snapshot
+    fn stored(&self, i: usize) -> u32 {
+        let offset = i * self.width.bytes();
+        let mut value = 0;
+        for b in &self.snapshot[offset..offset + self.width.bytes()] {
+            value = (value << 8) | *b as u32;
+        }
+        value
+    }
+
+    fn store(&mut self, i: usize, value: u32) {
+        let bytes = value.to_be_bytes();
+        let width = self.width.bytes();
+        let offset = i * width;
+        self.snapshot[offset..offset + width].copy_from_slice(&bytes[4 - width..]);
+    }
+
+    /**
+     * Takes a snapshot of len bytes at start
+     * and marks every readable element as a candidate.
+     * Returns the number of bytes covered which
+     * may be less than len if the buffers are too small.
+     */
+    pub fn begin<M: MemoryAccess>(
+        &mut self,
+        memory: &M,
+        start: usize,
+        len: usize,
+        width: SearchWidth,
+    ) -> usize {
+        self.width = width;
+        self.start = start & !(width.bytes() - 1);
+        self.len = usize::min(len, self.capacity(width)) / width.bytes() * width.bytes();
+        self.candidates = 0;
+        self.bitmap.fill(0);
+
+        for i in 0..self.elements() {
+            if let Ok(value) = self.read(memory, self.addr(i)) {
+                self.store(i, value);
+                self.bitmap[i / 8] |= 1 << (i % 8);
+                self.candidates += 1;
+            }
+        }
+
+        self.len
+    }
+
+    /**
+     * Removes all candidates that do not match filter
+     * and updates the snapshot of the remaining ones
+     * Returns the number of remaining candidates
+     */
+    pub fn filter<M: MemoryAccess>(&mut self, memory: &M, filter: Filter) -> usize {
+        for i in 0..self.elements() {
+            if !self.is_candidate(i) {
+                continue;
+            }
+
+            match self.read(memory, self.addr(i)) {
+                Ok(current) if filter.matches(self.stored(i), current) => self.store(i, current),
+                _ => self.clear_candidate(i),
+            }
+        }
+
+        self.candidates
+    }
+
+    /// addresses of all remaining candidates
+    pub fn results(&self) -> impl Iterator<Item = usize> + '_ {
+        let elements = self.elements();
+
+        // whole bytes without candidates are skipped
+        self.bitmap[..elements.div_ceil(8)]
+            .iter()
+            .enumerate()
+            .filter(|(_, bits)| **bits != 0)
+            .flat_map(|(byte, bits)| {
+                (0..8)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| byte * 8 + bit)
+            })
+            .filter(move |i| *i < elements)
+            .map(move |i| self.addr(i))
+    }
+
+    /// value of the candidate at addr as of the last filter
+    pub fn value(&self, addr: usize) -> Option<u32> {
+        if addr < self.start || addr >= self.start + self.len {
+            return None;
+        }
+        let i = (addr - self.start) / self.width.bytes();
+        if self.is_candidate(i) {
+            Some(self.stored(i))
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * A widget to drive a RamSearch
+ * left and right select the filter,
+ * up and down move through the results.
+ * When the buffers cannot hold all of rdram the search covers
+ * a window of it, next_window pages through the rest.
+ */
+pub struct CheatFinder<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    rows: usize,
+    cursor: usize,
+    filter: usize,
+    width: SearchWidth,
+    /// offset of the searched window in rdram
+    window: usize,
+    toggle_timer_max: u16,
+    toggle_timer: u16,
+    close_action: EntryTypes<T>,
+    back_action: EntryTypes<T>,
+    value_buffer: [u8; 9],
+    keyboard: Keyboard<'static, T>,
+    pub memory: MemoryMap,
+    pub search: RamSearch<'static>,
+}
+
+impl<T> CheatFinder<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(
+        x: isize,
+        y: isize,
+        search: RamSearch<'static>,
+        close_action: EntryTypes<T>,
+        back_action: EntryTypes<T>,
+    ) -> Self {
+        Self {
+            x,
+            y,
+            active: false,
+            rows: 8,
+            cursor: 0,
+            filter: 0,
+            width: SearchWidth::U8,
+            window: 0,
+            toggle_timer_max: 10,
+            toggle_timer: 0,
+            close_action,
+            back_action,
+            value_buffer: [0; 9],
+            keyboard: Keyboard::new(x, y, &HEX),
+            memory: MemoryMap::detect(),
+            search,
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.left();
+        } else if self.filter == 0 {
+            self.filter = Filter::ALL.len() - 1;
+        } else {
+            self.filter -= 1;
+        }
+    }
+
+    pub fn right(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.right();
+        } else {
+            self.filter = (self.filter + 1) % Filter::ALL.len();
+        }
+    }
+
+    pub fn up(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.up();
+        } else if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn down(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.down();
+        } else if self.cursor + 1 < self.search.candidates() {
+            self.cursor += 1;
+        }
+    }
+
+    /// width used by the next new search
+    pub fn cycle_width(&mut self) {
+        if !self.keyboard.active() {
+            self.width = self.width.next();
+        }
+    }
+
+    /// snapshots the current window of rdram and starts over
+    pub fn new_search(&mut self) {
+        if !self.keyboard.active() {
+            self.cursor = 0;
+            let len = self.memory.rdram_size - self.window;
+            self.search
+                .begin(&self.memory, KSEG0 + self.window, len, self.width);
+        }
+    }
+
+    /// moves the window to an address in rdram and starts a new search there
+    pub fn set_window(&mut self, addr: usize) {
+        let offset = addr & PHYS_MASK;
+        self.window = if offset < self.memory.rdram_size {
+            offset & !(self.width.bytes() - 1)
+        } else {
+            0
+        };
+        self.new_search();
+    }
+
+    /**
+     * Moves the window right behind the current one
+     * and starts a new search there
+     * wraps around at the end of rdram
+     */
+    pub fn next_window(&mut self) {
+        let step = self.search.capacity(self.width).max(self.width.bytes());
+        self.set_window(KSEG0 + self.window + step);
+    }
+
+    /**
+     * Applies the selected filter
+     * filters that compare against a value ask for it first
+     */
+    pub fn apply(&mut self) {
+        if self.keyboard.active() {
+            return;
+        }
+
+        let filter = Filter::ALL[self.filter];
+        if filter.needs_value() {
+            self.keyboard.reset(&mut self.value_buffer, 0);
+            self.keyboard.active = true;
+        } else {
+            self.run(filter);
+        }
+    }
+
+    fn run(&mut self, filter: Filter) {
+        self.search.filter(&self.memory, filter);
+        self.cursor = 0;
+    }
+
+    pub fn select(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.select(&mut self.value_buffer[..8]);
+        }
+    }
+
+    pub fn enter(&mut self) {
+        if self.keyboard.active() {
+            self.keyboard.enter();
+        }
+    }
+
+    /// address of the result under the cursor
+    pub fn selected(&self) -> Option<usize> {
+        self.search.results().nth(self.cursor)
+    }
+
+    /// opens the monitor at the selected result
+    pub fn to_monitor(&self, monitor: &mut Monitor<T>) {
+        if let Some(addr) = self.selected() {
+            monitor.goto(addr);
+        }
+    }
+
+    /// a watch for the selected result in the searched width
+    pub fn to_watch(&self) -> Option<WatchType> {
+        let addr = self.selected()?;
+        Some(match self.search.width() {
+            SearchWidth::U8 => WatchType::UInt8(addr as *const u8),
+            SearchWidth::U16 => WatchType::UInt16(addr as *const u16),
+            SearchWidth::U32 => WatchType::UInt32(addr as *const u32),
+        })
+    }
+
+    pub fn open(&mut self) {
+        self.active = true;
+    }
+
+    pub fn close(&mut self, data: T) {
+        self.active = false;
+        self.close_action.activate(data);
+    }
+
+    pub fn back(&mut self, data: T) {
+        if self.keyboard.active() {
+            self.keyboard.back(&mut self.value_buffer);
+        } else {
+            self.toggle_timer = 0;
+            self.back_action.activate(data);
+        }
+    }
+}
+
+impl<T> Drawable<T> for CheatFinder<T>
+where
+    T: Copy + Clone,
+{
+    fn update(&mut self, data: T) {
+        if self.toggle_timer > 0 {
+            self.toggle_timer -= 1;
+        }
+
+        if !self.active {
+            return;
+        }
+
+        if self.keyboard.active() {
+            self.keyboard.update(data);
+        } else if self.keyboard.enter {
+            self.keyboard.enter = false;
+            // this input cannot fail because of the restricted keyboard input
+            let value = Parser::from_hexu(&self.value_buffer).unwrap_or(0) as u32;
+            self.run(Filter::ALL[self.filter].with_value(value));
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        if self.keyboard.active() {
+            self.keyboard.draw_buffer(ctxt, &self.value_buffer);
+            self.keyboard.draw(ctxt);
+            return;
+        }
+
+        // header: filter, width, number of candidates and start of the window
+        let filter = Filter::ALL[self.filter];
+        ctxt.puts(filter.name(), self.x, self.y);
+        let width = [b'W', b'0' + self.width.bytes() as u8, 0];
+        ctxt.putsu8(&width, self.x + 10 * ctxt.char_width(), self.y);
+        let count_x = self.x + 13 * ctxt.char_width();
+        draw_hex(ctxt, self.search.candidates() as u64, 8, count_x, self.y);
+        let window_x = self.x + 22 * ctxt.char_width();
+        draw_hex(ctxt, self.search.start() as u64, 8, window_x, self.y);
+
+        let digits = self.search.width().bytes() * 2;
+        let value_x = self.x + 9 * ctxt.char_width();
+        let first = self.cursor - self.cursor % self.rows;
+        for (r, addr) in self
+            .search
+            .results()
+            .skip(first)
+            .take(self.rows)
+            .enumerate()
+        {
+            let y = self.y + ctxt.char_height() * (r as isize + 1);
+
+            ctxt.set_color(Color::new(0xFF, 0xFF, 0x00, 0xFF));
+            draw_hex(ctxt, addr as u64, 8, self.x, y);
+
+            if first + r == self.cursor {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            let value = self.search.value(addr).unwrap_or(0);
+            draw_hex(ctxt, value as u64, digits, value_x, y);
+        }
+    }
+}
+
+impl<T> Widget<T> for CheatFinder<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, data: T) {
+        if self.toggle_timer > 0 {
+            return;
+        }
+
+        self.toggle_timer = self.toggle_timer_max;
+        self.keyboard.active = false;
+        if self.active {
+            self.close(data);
+        } else {
+            self.open();
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -0,0 +1,261 @@
+use crate::interrupt::{disable_int, enable_int};
+use crate::joybus::{
+    check_data_crc, Accessory, Command, DeviceInfo, DeviceType, JoybusError, PifBlock,
+    ACCESSORY_BLOCK_LEN, PIF_RAM_LEN, PORTS,
+};
+use crate::usb::{data_cache_hit_writeback, data_cache_hit_writeback_invalidate};
+use core::ffi::c_void;
+use core::ptr::{read_volatile, write_volatile};
+
+const SI_DRAM_ADDR: *mut u32 = 0xA4800000 as *mut u32;
+const SI_PIF_ADDR_RD64B: *mut u32 = 0xA4800004 as *mut u32;
+const SI_PIF_ADDR_WR64B: *mut u32 = 0xA4800010 as *mut u32;
+const SI_STATUS: *mut u32 = 0xA4800018 as *mut u32;
+
+const SI_STATUS_DMA_BUSY: u32 = 1 << 0;
+const SI_STATUS_IO_BUSY: u32 = 1 << 1;
+
+/// pending interrupts of the rcp
+const MI_INTR: *const u32 = 0xA4300008 as *const u32;
+const MI_INTR_SI: u32 = 1 << 1;
+
+/// physical address of pif ram
+const PIF_RAM_ADDR: u32 = 0x1FC007C0;
+
+/// the rumble pak motor and the transfer pak power are switched at this address
+const ACCESSORY_PROBE_ADDR: u16 = 0x8000;
+const PROBE_OFF: u8 = 0xFE;
+const PROBE_RUMBLE: u8 = 0x80;
+const PROBE_TRANSFER: u8 = 0x84;
+const RUMBLE_ADDR: u16 = 0xC000;
+
+fn si_busy() -> bool {
+    unsafe { read_volatile(SI_STATUS) & (SI_STATUS_DMA_BUSY | SI_STATUS_IO_BUSY) != 0 }
+}
+
+/// the game has not handled the interrupt of its last transfer yet
+fn si_interrupt_pending() -> bool {
+    unsafe { read_volatile(MI_INTR) & MI_INTR_SI != 0 }
+}
+
+/**
+ * Copies a block to or from pif ram
+ * Writing SI_STATUS acknowledges the interrupt of this transfer,
+ * so only use it when the game has no si interrupt pending.
+ */
+unsafe fn si_dma(block: &mut PifBlock, pif_reg: *mut u32) {
+    let data = block.data.as_mut_ptr() as *mut c_void;
+    if pif_reg == SI_PIF_ADDR_RD64B {
+        data_cache_hit_writeback_invalidate(data, PIF_RAM_LEN);
+    } else {
+        data_cache_hit_writeback(data, PIF_RAM_LEN);
+    }
+
+    write_volatile(SI_DRAM_ADDR, data as u32 & 0x1FFFFFFF);
+    write_volatile(pif_reg, PIF_RAM_ADDR);
+    while si_busy() {}
+    write_volatile(SI_STATUS, 0);
+}
+
+/**
+ * Sends a block to pif ram, runs it and reads the responses back
+ * Returns Busy without touching the si while the game's own transfer
+ * is running or its interrupt has not been handled yet.
+ * The game may also be between writing its command block and reading
+ * the result, so pif ram is saved first and written back afterwards.
+ * Interrupts are off meanwhile so the game can not start a transfer.
+ */
+pub fn exchange(block: &mut PifBlock) -> Result<(), JoybusError> {
+    unsafe {
+        let flags = disable_int();
+        if si_busy() || si_interrupt_pending() {
+            enable_int(flags);
+            return Err(JoybusError::Busy);
+        }
+
+        let mut saved = PifBlock::new();
+        si_dma(&mut saved, SI_PIF_ADDR_RD64B);
+
+        si_dma(block, SI_PIF_ADDR_WR64B);
+        si_dma(block, SI_PIF_ADDR_RD64B);
+
+        si_dma(&mut saved, SI_PIF_ADDR_WR64B);
+
+        enable_int(flags);
+    }
+    Ok(())
+}
+
+/// what is known about a port
+#[derive(Copy, Clone)]
+pub struct PortState {
+    pub info: Result<DeviceInfo, JoybusError>,
+    pub accessory: Accessory,
+    /// the last controller read, 0xFFFFFFFF like in pif ram when it failed
+    pub status: u32,
+    pub error: Option<JoybusError>,
+}
+
+impl PortState {
+    pub const fn new() -> Self {
+        Self {
+            info: Err(JoybusError::NoDevice),
+            accessory: Accessory::None,
+            status: 0xFFFFFFFF,
+            error: None,
+        }
+    }
+
+    pub fn is_controller(&self) -> bool {
+        matches!(self.info, Ok(info) if info.device == DeviceType::Controller)
+    }
+}
+
+impl Default for PortState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Talks to the devices on all four ports through the si
+ * without relying on the game to read its controllers
+ * Call detect when controllers may have changed
+ * and poll once a frame, both do nothing while the game uses the si.
+ */
+pub struct Controllers {
+    pub ports: [PortState; PORTS],
+}
+
+impl Controllers {
+    pub const fn new() -> Self {
+        Self {
+            ports: [PortState::new(); PORTS],
+        }
+    }
+
+    /// the last controller status of a port, can be passed to InputHandler::new
+    pub fn status_ptr(&mut self, port: usize) -> *mut u32 {
+        &mut self.ports[port].status as *mut u32
+    }
+
+    /// asks every port for its device and identifies accessories of controllers
+    pub fn detect(&mut self) {
+        let mut block = match PifBlock::all(Command::Info) {
+            Ok(block) => block,
+            Err(_) => return,
+        };
+        if exchange(&mut block).is_err() {
+            return;
+        }
+
+        for port in 0..PORTS {
+            let info = block.info(port);
+            self.ports[port].info = info;
+            self.ports[port].error = info.err();
+            self.ports[port].accessory = Accessory::None;
+
+            if let Ok(info) = info {
+                if info.device == DeviceType::Controller && info.has_accessory() {
+                    match self.identify_accessory(port) {
+                        Ok(accessory) => self.ports[port].accessory = accessory,
+                        Err(err) => self.ports[port].error = Some(err),
+                    }
+                }
+            }
+        }
+    }
+
+    /// reads the buttons and stick of every controller, nothing changes while the si is busy
+    pub fn poll(&mut self) {
+        let mut block = match PifBlock::all(Command::ReadButtons) {
+            Ok(block) => block,
+            Err(_) => return,
+        };
+        if exchange(&mut block).is_err() {
+            return;
+        }
+
+        for (port, state) in self.ports.iter_mut().enumerate() {
+            match block.buttons(port) {
+                Ok(status) => {
+                    state.status = status;
+                    state.error = None;
+                }
+                Err(err) => {
+                    state.status = 0xFFFFFFFF;
+                    state.error = Some(err);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, port: usize, command: Command) -> Result<PifBlock, JoybusError> {
+        let mut block = PifBlock::single(port, command)?;
+        exchange(&mut block)?;
+        if let Err(err) = block.response(port) {
+            self.ports[port].error = Some(err);
+            return Err(err);
+        }
+        Ok(block)
+    }
+
+    /// 32 bytes from the accessory at addr
+    pub fn read_accessory(
+        &mut self,
+        port: usize,
+        addr: u16,
+    ) -> Result<[u8; ACCESSORY_BLOCK_LEN], JoybusError> {
+        self.run(port, Command::ReadAccessory(addr))?
+            .accessory_data(port)
+    }
+
+    pub fn write_accessory(
+        &mut self,
+        port: usize,
+        addr: u16,
+        data: [u8; ACCESSORY_BLOCK_LEN],
+    ) -> Result<(), JoybusError> {
+        let block = self.run(port, Command::WriteAccessory(addr, data))?;
+        check_data_crc(&data, block.response(port)?[0])
+    }
+
+    /**
+     * Finds out what accessory is inserted by writing to the probe address
+     * A rumble pak reads back 0x80, a powered transfer pak 0x84.
+     * Everything else is taken as a controller pak.
+     */
+    pub fn identify_accessory(&mut self, port: usize) -> Result<Accessory, JoybusError> {
+        let probe = |this: &mut Self, value: u8| -> Result<u8, JoybusError> {
+            this.write_accessory(port, ACCESSORY_PROBE_ADDR, [value; ACCESSORY_BLOCK_LEN])?;
+            Ok(this.read_accessory(port, ACCESSORY_PROBE_ADDR)?[0])
+        };
+
+        let result = match probe(self, PROBE_OFF) {
+            Err(JoybusError::NoAccessory) => return Ok(Accessory::None),
+            Err(err) => return Err(err),
+            Ok(_) if probe(self, PROBE_RUMBLE)? == PROBE_RUMBLE => Accessory::RumblePak,
+            Ok(_) if probe(self, PROBE_TRANSFER)? == PROBE_TRANSFER => {
+                // keep the transfer pak turned off
+                probe(self, PROBE_OFF)?;
+                Accessory::TransferPak
+            }
+            Ok(_) => Accessory::ControllerPak,
+        };
+        Ok(result)
+    }
+
+    /// turns the motor of a rumble pak on or off
+    pub fn rumble(&mut self, port: usize, on: bool) -> Result<(), JoybusError> {
+        if self.ports[port].accessory != Accessory::RumblePak {
+            return Err(JoybusError::NoAccessory);
+        }
+        self.write_accessory(port, RUMBLE_ADDR, [on as u8; ACCESSORY_BLOCK_LEN])
+    }
+}
+
+impl Default for Controllers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,413 @@
+use crate::clock::{write_time, TimeFormat};
+use crate::color::Color;
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::save::{self, SaveBuffer, SaveError, BLOCK_HEADER_LEN};
+use crate::text::TextWriter;
+use crate::timer::Timer;
+
+pub const MAX_SEGMENTS: usize = 16;
+
+const SAVE_MAGIC: [u8; 4] = *b"SPLT";
+
+/// block header, segment count and padding, pb and best times
+pub const SAVE_LEN: usize = BLOCK_HEADER_LEN + 8 + MAX_SEGMENTS * 8;
+
+/// stored in place of a missing time
+const NO_TIME: u32 = u32::MAX;
+
+/// width of the name column in characters
+const NAME_LEN: isize = 12;
+/// width of the time column in characters
+const TIME_LEN: isize = 10;
+
+const GOLD: Color = Color {
+    red: 0xFF,
+    green: 0xD7,
+    blue: 0x00,
+    alpha: 0xFF,
+};
+
+fn encode_time(time: Option<u32>, buffer: &mut [u8]) {
+    buffer.copy_from_slice(&time.unwrap_or(NO_TIME).to_be_bytes());
+}
+
+fn decode_time(buffer: &[u8]) -> Option<u32> {
+    match u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) {
+        NO_TIME => None,
+        time => Some(time),
+    }
+}
+
+/// writes a signed time difference as +m:ss.mmm or -m:ss.mmm
+pub fn write_delta(delta: i64, w: &mut TextWriter) {
+    w.push(if delta < 0 { b'-' } else { b'+' });
+    write_time(delta.unsigned_abs(), TimeFormat::Millis, w);
+}
+
+/**
+ * The times a run is compared against
+ * all times are in milliseconds
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub struct Comparison {
+    pub count: usize,
+    /// split times (from the start of the run) of the personal best
+    pub pb: [Option<u32>; MAX_SEGMENTS],
+    /// the fastest time of every single segment
+    pub best: [Option<u32>; MAX_SEGMENTS],
+}
+
+impl Comparison {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: count.min(MAX_SEGMENTS),
+            pb: [None; MAX_SEGMENTS],
+            best: [None; MAX_SEGMENTS],
+        }
+    }
+
+    /// None until every segment has a best time
+    pub fn sum_of_best(&self) -> Option<u64> {
+        self.best[..self.count]
+            .iter()
+            .try_fold(0u64, |sum, best| Some(sum + (*best)? as u64))
+    }
+
+    /**
+     * Takes the best segments of a run
+     * and the whole run if it was finished and faster than the pb
+     */
+    pub fn merge(&mut self, times: &[Option<u32>; MAX_SEGMENTS], finished: bool) {
+        for i in 0..self.count {
+            if let Some(segment) = segment_time(times, i) {
+                match self.best[i] {
+                    Some(best) if best <= segment => {}
+                    _ => self.best[i] = Some(segment),
+                }
+            }
+        }
+
+        let last = match self.count.checked_sub(1) {
+            Some(last) => last,
+            None => return,
+        };
+        if let (true, Some(time)) = (finished, times[last]) {
+            match self.pb[last] {
+                Some(pb) if pb <= time => {}
+                _ => self.pb = *times,
+            }
+        }
+    }
+
+    pub fn encode(&self, block: &mut [u8]) {
+        let data = &mut block[BLOCK_HEADER_LEN..];
+        data[0] = self.count as u8;
+        data[1..8].fill(0);
+
+        let times = self.pb.iter().chain(self.best.iter());
+        for (time, buffer) in times.zip(data[8..].chunks_exact_mut(4)) {
+            encode_time(*time, buffer);
+        }
+    }
+
+    pub fn decode(block: &[u8]) -> Option<Self> {
+        let data = &block[BLOCK_HEADER_LEN..];
+        let count = data[0] as usize;
+        if count > MAX_SEGMENTS {
+            return None;
+        }
+
+        let mut comparison = Self::new(count);
+        let mut times = data[8..].chunks_exact(4).map(decode_time);
+        for (pb, time) in comparison.pb.iter_mut().zip(&mut times) {
+            *pb = time;
+        }
+        for (best, time) in comparison.best.iter_mut().zip(&mut times) {
+            *best = time;
+        }
+        Some(comparison)
+    }
+}
+
+/// the time of a single segment or None if it or the one before was skipped
+pub fn segment_time(times: &[Option<u32>; MAX_SEGMENTS], index: usize) -> Option<u32> {
+    let previous = match index {
+        0 => 0,
+        _ => times[index - 1]?,
+    };
+    Some(times[index]?.saturating_sub(previous))
+}
+
+/**
+ * Speedrun splits
+ * Every split stores the time since the start of the run,
+ * skipped segments have no time.
+ * Reset merges the run into the comparison and saves it when save_offset is set.
+ */
+pub struct Splits<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    /// set timer.fields_per_frame when it uses TimeSource::Frames
+    pub timer: Timer<T>,
+    names: &'static [&'static str],
+    times: [Option<u32>; MAX_SEGMENTS],
+    current: usize,
+    pub comparison: Comparison,
+    /// visible segments
+    pub rows: usize,
+    /**
+     * Where the comparison is stored in sram, nothing is saved while this is None
+     * The offset has to be outside of the game's own save, and evd_init
+     * turns the cart's save type off, so pick it for the game being run.
+     */
+    pub save_offset: Option<usize>,
+    pub last_error: Option<SaveError>,
+}
+
+impl<T> Splits<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, names: &'static [&'static str]) -> Self {
+        let count = names.len().min(MAX_SEGMENTS);
+        Self {
+            x,
+            y,
+            active: false,
+            timer: Timer::new(x, y, 1),
+            names: &names[..count],
+            times: [None; MAX_SEGMENTS],
+            current: 0,
+            comparison: Comparison::new(count),
+            rows: 8,
+            save_offset: None,
+            last_error: None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn finished(&self) -> bool {
+        self.current >= self.count()
+    }
+
+    pub fn time(&self, index: usize) -> Option<u32> {
+        self.times[index]
+    }
+
+    /// difference to the personal best at this split
+    pub fn delta(&self, index: usize) -> Option<i64> {
+        Some(self.times[index]? as i64 - self.comparison.pb[index]? as i64)
+    }
+
+    /// true if the segment beat the best segment
+    pub fn is_gold(&self, index: usize) -> bool {
+        match (
+            segment_time(&self.times, index),
+            self.comparison.best[index],
+        ) {
+            (Some(segment), Some(best)) => segment < best,
+            _ => false,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.current == 0 && !self.timer.running() {
+            self.timer.reset();
+            self.timer.start();
+        }
+    }
+
+    /// starts the run if it is not running yet
+    pub fn split(&mut self) {
+        if self.finished() {
+            return;
+        }
+        if !self.timer.running() {
+            self.start();
+            return;
+        }
+
+        self.times[self.current] = Some(self.timer.elapsed() as u32);
+        self.current += 1;
+        if self.finished() {
+            self.timer.stop();
+        }
+    }
+
+    /**
+     * Takes back the last split
+     * a finished run keeps running, the time between
+     * the last split and the undo is not counted
+     */
+    pub fn undo(&mut self) {
+        if self.current == 0 {
+            return;
+        }
+
+        if self.finished() {
+            self.timer.start();
+        }
+        self.current -= 1;
+        self.times[self.current] = None;
+    }
+
+    /// the last segment can not be skipped
+    pub fn skip(&mut self) {
+        if self.timer.running() && self.current + 1 < self.count() {
+            self.times[self.current] = None;
+            self.current += 1;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if self.current > 0 {
+            self.comparison.merge(&self.times, self.finished());
+            self.save();
+        }
+
+        self.times = [None; MAX_SEGMENTS];
+        self.current = 0;
+        self.timer.stop();
+        self.timer.reset();
+    }
+
+    /// does nothing unless save_offset is set
+    pub fn save(&mut self) {
+        let offset = match self.save_offset {
+            Some(offset) => offset,
+            None => return,
+        };
+        let mut block = SaveBuffer::<SAVE_LEN>::new();
+        self.comparison.encode(&mut block.data);
+        self.last_error = save::store(offset, SAVE_MAGIC, &mut block.data).err();
+    }
+
+    /// keeps the current comparison if nothing valid was saved
+    pub fn load(&mut self) {
+        let offset = match self.save_offset {
+            Some(offset) => offset,
+            None => return,
+        };
+        let mut block = SaveBuffer::<SAVE_LEN>::new();
+        self.last_error = save::load(offset, SAVE_MAGIC, &mut block.data).err();
+        if self.last_error.is_some() {
+            return;
+        }
+
+        match Comparison::decode(&block.data) {
+            Some(comparison) if comparison.count == self.count() => self.comparison = comparison,
+            _ => self.last_error = Some(SaveError::Invalid),
+        }
+    }
+
+    /// the first visible segment, keeps the next split in view
+    fn first_row(&self) -> usize {
+        let next = (self.current + 1).min(self.count());
+        next.saturating_sub(self.rows)
+    }
+
+    fn draw_row(&self, ctxt: &mut dyn RenderContext, index: usize, y: isize) {
+        let mut line = [0u8; 16];
+        let time_x = self.x + NAME_LEN * ctxt.char_width();
+        let delta_x = time_x + TIME_LEN * ctxt.char_width();
+
+        if index == self.current {
+            ctxt.set_color(Color::new(0x00, 0xFF, 0xFF, 0xFF));
+        }
+        ctxt.puts(self.names[index], self.x, y);
+
+        // the run time if split, otherwise the pb time
+        let time = self.times[index].or(self.comparison.pb[index]);
+        let mut w = TextWriter::new(&mut line);
+        match time {
+            Some(time) => write_time(time as u64, TimeFormat::Millis, &mut w),
+            None => w.puts("-"),
+        }
+        w.finish();
+        ctxt.putsu8(&line, time_x, y);
+
+        if let Some(delta) = self.delta(index) {
+            let mut w = TextWriter::new(&mut line);
+            write_delta(delta, &mut w);
+            w.finish();
+
+            if self.is_gold(index) {
+                ctxt.set_color(GOLD);
+            } else if delta < 0 {
+                ctxt.set_color(Color::new(0x00, 0xFF, 0x00, 0xFF));
+            } else {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            }
+            ctxt.putsu8(&line, delta_x, y);
+        }
+    }
+}
+
+impl<T> Drawable<T> for Splits<T>
+where
+    T: Copy + Clone,
+{
+    /// call once a game frame
+    fn update(&mut self, data: T) {
+        self.timer.update(data);
+        self.timer.inc();
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        let first = self.first_row();
+        let last = (first + self.rows).min(self.count());
+        let mut y = self.y;
+        for index in first..last {
+            self.draw_row(ctxt, index, y);
+            y += ctxt.char_height();
+        }
+
+        // total time
+        let mut line = [0u8; 24];
+        let mut w = TextWriter::new(&mut line);
+        self.timer.write(&mut w);
+        w.finish();
+        if !self.timer.running() {
+            ctxt.set_color(Color::new(0x80, 0x80, 0x80, 0xFF));
+        }
+        ctxt.putsu8(&line, self.x + NAME_LEN * ctxt.char_width(), y);
+        y += ctxt.char_height();
+
+        let mut w = TextWriter::new(&mut line);
+        w.puts("SoB ");
+        match self.comparison.sum_of_best() {
+            Some(sum) => write_time(sum, TimeFormat::Millis, &mut w),
+            None => w.puts("-"),
+        }
+        w.finish();
+        ctxt.putsu8(&line, self.x, y);
+    }
+}
+
+impl<T> Widget<T> for Splits<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -0,0 +1,273 @@
+use crate::color::Color;
+use crate::input::{
+    magnitude, stick_angle, Button, InputHandler, StickCalibration, CONTROLLERS, STICK_RANGE,
+};
+use crate::math::isqrt;
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::text::TextWriter;
+use core::marker::PhantomData;
+
+/**
+ * Calibrates the stick of a controller and tests its range
+ * A takes the current position as the center,
+ * B forgets the range seen so far and Z uses it as the stick range.
+ * C-up/C-down change the deadzone, C-right/C-left the menu deadzone.
+ */
+pub struct StickCalibrator<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    port: usize,
+    input: InputHandler,
+    /// raw extent seen since the last reset
+    min: (i8, i8),
+    max: (i8, i8),
+    phantom: PhantomData<T>,
+}
+
+impl<T> StickCalibrator<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, port: usize) -> Self {
+        let port = port.min(CONTROLLERS.len() - 1);
+        Self {
+            x,
+            y,
+            active: false,
+            port,
+            input: InputHandler::new(CONTROLLERS[port]),
+            min: (0, 0),
+            max: (0, 0),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn port(&self) -> usize {
+        self.port
+    }
+
+    /// ports are 0 to 3, the calibration is kept
+    pub fn set_port(&mut self, port: usize) {
+        if port < CONTROLLERS.len() {
+            let calibration = self.input.calibration;
+            self.port = port;
+            self.input = InputHandler::new(CONTROLLERS[port]);
+            self.input.calibration = calibration;
+            self.reset_range();
+        }
+    }
+
+    /// copy this into the InputHandler of the port
+    pub fn calibration(&self) -> StickCalibration {
+        self.input.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: StickCalibration) {
+        self.input.calibration = calibration;
+    }
+
+    pub fn capture_center(&mut self) {
+        let (x, y) = InputHandler::raw_stick(self.input.current());
+        self.input.calibration.center_x = x;
+        self.input.calibration.center_y = y;
+        self.reset_range();
+    }
+
+    pub fn reset_range(&mut self) {
+        let center = (
+            self.input.calibration.center_x,
+            self.input.calibration.center_y,
+        );
+        self.min = center;
+        self.max = center;
+    }
+
+    /// the shortest reach from the center so every direction gets to full range
+    pub fn measured_range(&self) -> u8 {
+        let cal = &self.input.calibration;
+        let (cx, cy) = (cal.center_x as i32, cal.center_y as i32);
+        let reach = [
+            self.max.0 as i32 - cx,
+            cx - self.min.0 as i32,
+            self.max.1 as i32 - cy,
+            cy - self.min.1 as i32,
+        ];
+        reach
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or(0)
+            .clamp(0, u8::MAX as i32) as u8
+    }
+
+    /// uses the measured range, ignored until the stick went around once
+    pub fn apply_range(&mut self) {
+        let range = self.measured_range();
+        if range > 0 {
+            self.input.calibration.range = range;
+        }
+    }
+
+    fn poll(&mut self) {
+        let (x, y) = InputHandler::raw_stick(self.input.current());
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+
+        let input = &mut self.input;
+        if input.read_button(Button::AInput, true) {
+            self.capture_center();
+        } else if input.read_button(Button::BInput, true) {
+            self.reset_range();
+        } else if input.read_button(Button::ZInput, true) {
+            self.apply_range();
+        } else if input.read_button(Button::CUInput, true) {
+            input.calibration.deadzone = input.calibration.deadzone.saturating_add(1);
+        } else if input.read_button(Button::CDInput, true) {
+            input.calibration.deadzone = input.calibration.deadzone.saturating_sub(1);
+        } else if input.read_button(Button::CRInput, true) {
+            input.calibration.nav_deadzone = input.calibration.nav_deadzone.saturating_add(1);
+        } else if input.read_button(Button::CLInput, true) {
+            input.calibration.nav_deadzone = input.calibration.nav_deadzone.saturating_sub(1);
+        }
+    }
+
+    fn draw_box(&self, ctxt: &mut dyn RenderContext, x: isize, y: isize, size: isize) {
+        let half = size / 2;
+        let range = STICK_RANGE as isize;
+        // up is positive on the stick
+        let to_screen = |sx: isize, sy: isize| {
+            (
+                x + half + sx.clamp(-range, range) * half / range,
+                y + half - sy.clamp(-range, range) * half / range,
+            )
+        };
+
+        if !ctxt.fill_rect(x, y, size, size, Color::new(0x30, 0x30, 0x30, 0xFF)) {
+            return;
+        }
+
+        let cal = &self.input.calibration;
+        // the deadzone is a circle around the center like in apply, drawn row by row
+        let radius = (cal.deadzone as isize).min(range) * half / range;
+        for dy in -radius..=radius {
+            let dx = isqrt((radius * radius - dy * dy) as u32) as isize;
+            ctxt.fill_rect(
+                x + half - dx,
+                y + half + dy,
+                2 * dx + 1,
+                1,
+                Color::new(0x50, 0x50, 0x50, 0xFF),
+            );
+        }
+
+        let line = Color::new(0x60, 0x60, 0x60, 0xFF);
+        ctxt.fill_rect(x + half, y, 1, size, line);
+        ctxt.fill_rect(x, y + half, size, 1, line);
+
+        let (sx, sy) = self.input.stick();
+        let (dot_x, dot_y) = to_screen(sx as isize, sy as isize);
+        ctxt.fill_rect(
+            dot_x - 2,
+            dot_y - 2,
+            4,
+            4,
+            Color::new(0xFF, 0xFF, 0xFF, 0xFF),
+        );
+    }
+}
+
+impl<T> Drawable<T> for StickCalibrator<T>
+where
+    T: Copy + Clone,
+{
+    fn update(&mut self, _data: T) {
+        if self.active {
+            unsafe {
+                self.input.update();
+            }
+            self.poll();
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        let ch = ctxt.char_height();
+        let (raw_x, raw_y) = InputHandler::raw_stick(self.input.current());
+        let (x, y) = self.input.stick();
+        let cal = self.input.calibration;
+
+        let mut line = [0u8; 40];
+        let mut w = TextWriter::new(&mut line);
+        w.puts("raw x:");
+        w.signed(raw_x as i64);
+        w.puts(" y:");
+        w.signed(raw_y as i64);
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y);
+
+        let mut line = [0u8; 40];
+        let mut w = TextWriter::new(&mut line);
+        w.puts("cal x:");
+        w.signed(x as i64);
+        w.puts(" y:");
+        w.signed(y as i64);
+        w.puts(" mag:");
+        w.dec(magnitude(x, y) as u64);
+        w.puts(" ang:");
+        w.dec(stick_angle(x, y) as u64);
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y + ch);
+
+        let mut line = [0u8; 40];
+        let mut w = TextWriter::new(&mut line);
+        w.puts("x:");
+        w.signed(self.min.0 as i64);
+        w.puts("..");
+        w.signed(self.max.0 as i64);
+        w.puts(" y:");
+        w.signed(self.min.1 as i64);
+        w.puts("..");
+        w.signed(self.max.1 as i64);
+        w.puts(" reach:");
+        w.dec(self.measured_range() as u64);
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y + 2 * ch);
+
+        let mut line = [0u8; 40];
+        let mut w = TextWriter::new(&mut line);
+        w.puts("center ");
+        w.signed(cal.center_x as i64);
+        w.push(b',');
+        w.signed(cal.center_y as i64);
+        w.puts(" range ");
+        w.dec(cal.range as u64);
+        w.puts(" dz ");
+        w.dec(cal.deadzone as u64);
+        w.push(b'/');
+        w.dec(cal.nav_deadzone as u64);
+        w.finish();
+        ctxt.putsu8(&line, self.x, self.y + 3 * ch);
+
+        self.draw_box(ctxt, self.x, self.y + 4 * ch, 4 * ch);
+    }
+}
+
+impl<T> Widget<T> for StickCalibrator<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -0,0 +1,139 @@
+use crate::lag::FrameSource;
+use crate::memmap::MemoryAccess;
+use crate::save::SaveBuffer;
+use crate::usb::{BiError, Usb};
+use crate::watch::{WatchAddr, MAX_WATCHES};
+
+pub const RECORD_MAGIC: [u8; 2] = *b"TL";
+
+/// header plus index, type and 8 bytes for every watch
+pub const MAX_RECORD_LEN: usize = 8 + MAX_WATCHES * 10;
+
+/// set in the index byte when the watch could not be read
+pub const ERROR_FLAG: u8 = 0x80;
+
+/**
+ * Encodes one frame of watch values
+ * Header (8 bytes): "TL", value count, record length, frame counter (u32)
+ * Every active watch adds its index (with ERROR_FLAG if the read failed),
+ * its type id and, if the read worked, the raw value in the size of its type.
+ * Records are padded with zeros to a multiple of 4 bytes.
+ * Everything is big endian.
+ * Returns the length of the record.
+ */
+pub fn encode<T: Copy>(frame: u32, watches: &[Option<WatchAddr<T>>], buffer: &mut [u8]) -> usize {
+    let mut len = 8;
+    let mut count = 0;
+
+    for (index, watch) in watches.iter().enumerate().take(MAX_WATCHES) {
+        let watch = match watch {
+            Some(watch) if watch.active => watch,
+            _ => continue,
+        };
+
+        let size = watch.watch_type.size();
+        match watch.value() {
+            Ok(raw) => {
+                buffer[len] = index as u8;
+                buffer[len + 1] = watch.watch_type.id();
+                buffer[len + 2..len + 2 + size].copy_from_slice(&raw.to_be_bytes()[8 - size..]);
+                len += 2 + size;
+            }
+            Err(_) => {
+                buffer[len] = index as u8 | ERROR_FLAG;
+                buffer[len + 1] = watch.watch_type.id();
+                len += 2;
+            }
+        }
+        count += 1;
+    }
+
+    while len % 4 != 0 {
+        buffer[len] = 0;
+        len += 1;
+    }
+
+    buffer[0..2].copy_from_slice(&RECORD_MAGIC);
+    buffer[2] = count;
+    buffer[3] = len as u8;
+    buffer[4..8].copy_from_slice(&frame.to_be_bytes());
+    len
+}
+
+/**
+ * Streams watch values to the host every frame
+ * Records carry the game's frame number so the host
+ * can see frames that were dropped or skipped.
+ * Streaming stops on the first usb error
+ * so a missing host does not stall every frame.
+ */
+pub struct Telemetry {
+    pub active: bool,
+    /// where the frame number of a record comes from
+    pub source: FrameSource,
+    /// calls to send, the frame number for FrameSource::Hook
+    hooked: u32,
+    pub last_error: Option<BiError>,
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            source: FrameSource::Hook,
+            hooked: 0,
+            last_error: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: FrameSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        if self.active {
+            self.last_error = None;
+        }
+    }
+
+    /// the game's counter, or the hooked frames if it cannot be read
+    fn frame<M: MemoryAccess>(&self, memory: &M) -> u32 {
+        match self.source {
+            FrameSource::Hook => self.hooked,
+            FrameSource::Counter(counter) => counter
+                .read(memory)
+                .map(|value| value as u32)
+                .unwrap_or(self.hooked),
+        }
+    }
+
+    /**
+     * Sends one record
+     * call once a frame from the frame hook after the watches were read,
+     * also while streaming is off so hooked frames keep counting
+     */
+    pub fn send<T: Copy, M: MemoryAccess>(&mut self, memory: &M, watches: &[Option<WatchAddr<T>>]) {
+        let frame = self.frame(memory);
+        self.hooked = self.hooked.wrapping_add(1);
+        if !self.active {
+            return;
+        }
+
+        // usb dma needs an 8 byte aligned buffer
+        let mut record = SaveBuffer::<MAX_RECORD_LEN>::new();
+        let len = encode(frame, watches, &mut record.data);
+
+        if let Err(err) = Usb::write(&mut record.data[..len]) {
+            self.last_error = Some(err);
+            self.active = false;
+        }
+    }
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,167 @@
+use crate::render::RenderContext;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// digits after the decimal point for floats
+pub const FLOAT_DECIMALS: u32 = 3;
+
+/**
+ * Writes numbers and strings into a null terminated byte buffer
+ * without needing core::fmt.
+ * Anything that does not fit is dropped
+ */
+pub struct TextWriter<'a> {
+    buffer: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> TextWriter<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, pos: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == 0
+    }
+
+    pub fn push(&mut self, c: u8) {
+        // always keep space for the terminator
+        if self.pos + 1 < self.buffer.len() {
+            self.buffer[self.pos] = c;
+            self.pos += 1;
+        }
+    }
+
+    pub fn puts(&mut self, s: &str) {
+        for c in s.bytes() {
+            self.push(c);
+        }
+    }
+
+    /// writes bytes up to the first null
+    pub fn putsu8(&mut self, s: &[u8]) {
+        for c in s.iter().take_while(|c| **c != 0) {
+            self.push(*c);
+        }
+    }
+
+    /// writes exactly digits hex digits
+    pub fn hex(&mut self, value: u64, digits: usize) {
+        for i in (0..digits).rev() {
+            self.push(HEX_DIGITS[((value >> (i * 4)) & 0xF) as usize]);
+        }
+    }
+
+    /// writes value as 0x prefixed hex without leading zeros
+    pub fn hex_short(&mut self, value: u64) {
+        let mut digits = 1;
+        while digits < 16 && (value >> (digits * 4)) != 0 {
+            digits += 1;
+        }
+        self.puts("0x");
+        self.hex(value, digits);
+    }
+
+    pub fn signed_hex(&mut self, value: i64) {
+        if value < 0 {
+            self.push(b'-');
+        }
+        self.hex_short(value.unsigned_abs());
+    }
+
+    pub fn dec(&mut self, value: u64) {
+        let mut digits = [0u8; 20];
+        let mut len = 0;
+        let mut value = value;
+        loop {
+            digits[len] = b'0' + (value % 10) as u8;
+            len += 1;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+
+        for c in digits[..len].iter().rev() {
+            self.push(*c);
+        }
+    }
+
+    pub fn signed(&mut self, value: i64) {
+        if value < 0 {
+            self.push(b'-');
+        }
+        self.dec(value.unsigned_abs());
+    }
+
+    /**
+     * Writes a float with a fixed number of decimals
+     * values of 1e9 and above are written as x.xxxeN
+     */
+    pub fn float(&mut self, value: f32) {
+        if value.is_nan() {
+            self.puts("nan");
+            return;
+        }
+
+        if value.is_sign_negative() && value != 0.0 {
+            self.push(b'-');
+        }
+
+        let mut value = if value < 0.0 { -value } else { value };
+        if value.is_infinite() {
+            self.puts("inf");
+            return;
+        }
+
+        let mut exponent = 0;
+        if value >= 1e9 {
+            while value >= 10.0 {
+                value /= 10.0;
+                exponent += 1;
+            }
+        }
+
+        let scale = 10u64.pow(FLOAT_DECIMALS);
+        let mut whole = value as u64;
+        let mut fraction = ((value - whole as f32) * scale as f32 + 0.5) as u64;
+        if fraction >= scale {
+            whole += 1;
+            fraction -= scale;
+        }
+
+        self.dec(whole);
+        self.push(b'.');
+        let mut place = scale / 10;
+        while place > 0 {
+            self.push(b'0' + (fraction / place % 10) as u8);
+            place /= 10;
+        }
+
+        if exponent > 0 {
+            self.push(b'e');
+            self.dec(exponent);
+        }
+    }
+
+    /// terminates the string and returns its length
+    pub fn finish(self) -> usize {
+        if !self.buffer.is_empty() {
+            self.buffer[self.pos] = 0;
+        }
+        self.pos
+    }
+}
+
+/// draws exactly digits hex digits of value
+pub fn draw_hex(ctxt: &mut dyn RenderContext, value: u64, digits: usize, x: isize, y: isize) {
+    let mut buffer = [0u8; 17];
+    let mut w = TextWriter::new(&mut buffer);
+    w.hex(value, digits);
+    w.finish();
+    ctxt.putsu8(&buffer, x, y);
+}
@@ -0,0 +1,310 @@
+use crate::color::Color;
+use crate::keyboard::{Keyboard, DIGITS, HEX, LOWER, POINTER, SYMBOLS, UPPER};
+use crate::render::{Drawable, RenderContext, Widget};
+
+pub const MAX_TEXT_LEN: usize = 32;
+
+/**
+ * A named keyboard grid
+ */
+#[derive(Copy, Clone)]
+pub struct Layout {
+    pub name: &'static str,
+    pub grid: &'static [&'static [u8]],
+}
+
+pub static TEXT_LAYOUTS: [Layout; 4] = [
+    Layout {
+        name: "ABC",
+        grid: &UPPER,
+    },
+    Layout {
+        name: "abc",
+        grid: &LOWER,
+    },
+    Layout {
+        name: "123",
+        grid: &DIGITS,
+    },
+    Layout {
+        name: "#+-",
+        grid: &SYMBOLS,
+    },
+];
+
+pub static HEX_LAYOUTS: [Layout; 1] = [Layout {
+    name: "HEX",
+    grid: &HEX,
+}];
+
+/// hex digits and the operators of pointer chain expressions
+pub static POINTER_LAYOUTS: [Layout; 1] = [Layout {
+    name: "PTR",
+    grid: &POINTER,
+}];
+
+/// checks the whole text before it can be confirmed
+pub type ValidateFn = fn(text: &[u8]) -> bool;
+
+/// checks a single character before it is inserted
+pub type CharFilterFn = fn(c: u8) -> bool;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum InputResult {
+    Pending,
+    Confirmed,
+    Cancelled,
+}
+
+/**
+ * A text input with a movable cursor
+ * built on top of the on-screen keyboard.
+ * The shoulder buttons are expected to switch layouts
+ * and the text cursor is moved independently of the keyboard cursor.
+ */
+pub struct TextInput<T>
+where
+    T: Copy + Clone,
+{
+    x: isize,
+    y: isize,
+    pub active: bool,
+    buffer: [u8; MAX_TEXT_LEN + 1],
+    len: usize,
+    cursor: usize,
+    max_len: usize,
+    layouts: &'static [Layout],
+    layout: usize,
+    keyboard: Keyboard<'static, T>,
+    validate: Option<ValidateFn>,
+    char_filter: Option<CharFilterFn>,
+    result: InputResult,
+}
+
+impl<T> TextInput<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(x: isize, y: isize, max_len: usize, layouts: &'static [Layout]) -> Self {
+        Self {
+            x,
+            y,
+            active: false,
+            buffer: [0; MAX_TEXT_LEN + 1],
+            len: 0,
+            cursor: 0,
+            max_len: usize::min(max_len, MAX_TEXT_LEN),
+            layouts,
+            layout: 0,
+            keyboard: Keyboard::new(x, y + 2, layouts[0].grid),
+            validate: None,
+            char_filter: None,
+            result: InputResult::Pending,
+        }
+    }
+
+    pub fn with_validator(mut self, validate: ValidateFn) -> Self {
+        self.validate = Some(validate);
+        self
+    }
+
+    pub fn with_char_filter(mut self, char_filter: CharFilterFn) -> Self {
+        self.char_filter = Some(char_filter);
+        self
+    }
+
+    /**
+     * Starts a new input with text as the initial value
+     * the text cursor is placed at the end
+     */
+    pub fn open(&mut self, text: &[u8]) {
+        self.buffer.fill(0);
+        self.len = 0;
+        for c in text.iter().take_while(|c| **c != 0).take(self.max_len) {
+            self.buffer[self.len] = *c;
+            self.len += 1;
+        }
+        self.cursor = self.len;
+        self.result = InputResult::Pending;
+        self.active = true;
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// the null terminated text
+    pub fn c_text(&self) -> &[u8] {
+        &self.buffer[..=self.len]
+    }
+
+    /**
+     * Returns the result once and resets it
+     * to pending afterwards
+     */
+    pub fn take_result(&mut self) -> InputResult {
+        let result = self.result;
+        self.result = InputResult::Pending;
+        result
+    }
+
+    pub fn up(&mut self) {
+        self.keyboard.up();
+    }
+
+    pub fn down(&mut self) {
+        self.keyboard.down();
+    }
+
+    pub fn left(&mut self) {
+        self.keyboard.left();
+    }
+
+    pub fn right(&mut self) {
+        self.keyboard.right();
+    }
+
+    pub fn cursor_left(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn cursor_right(&mut self) {
+        if self.cursor < self.len {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn next_layout(&mut self) {
+        self.layout = (self.layout + 1) % self.layouts.len();
+        self.keyboard.set_grid(self.layouts[self.layout].grid);
+    }
+
+    pub fn prev_layout(&mut self) {
+        if self.layout == 0 {
+            self.layout = self.layouts.len() - 1;
+        } else {
+            self.layout -= 1;
+        }
+        self.keyboard.set_grid(self.layouts[self.layout].grid);
+    }
+
+    /// inserts c at the text cursor
+    pub fn insert(&mut self, c: u8) {
+        if self.len >= self.max_len {
+            return;
+        }
+
+        if let Some(filter) = self.char_filter {
+            if !filter(c) {
+                return;
+            }
+        }
+
+        self.buffer
+            .copy_within(self.cursor..self.len, self.cursor + 1);
+        self.buffer[self.cursor] = c;
+        self.cursor += 1;
+        self.len += 1;
+    }
+
+    /// inserts the key under the keyboard cursor
+    pub fn select(&mut self) {
+        self.insert(self.keyboard.current());
+    }
+
+    /// deletes the character before the text cursor
+    pub fn delete(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+
+        self.buffer
+            .copy_within(self.cursor..self.len, self.cursor - 1);
+        self.cursor -= 1;
+        self.len -= 1;
+        self.buffer[self.len] = 0;
+    }
+
+    /**
+     * Finishes the input if the validator accepts it
+     */
+    pub fn confirm(&mut self) {
+        if let Some(validate) = self.validate {
+            if !validate(self.text()) {
+                return;
+            }
+        }
+
+        self.result = InputResult::Confirmed;
+        self.active = false;
+    }
+
+    pub fn cancel(&mut self) {
+        self.result = InputResult::Cancelled;
+        self.active = false;
+    }
+
+    /**
+     * Deletes a character or cancels
+     * if the text is already empty
+     */
+    pub fn back(&mut self) {
+        if self.len == 0 {
+            self.cancel();
+        } else {
+            self.delete();
+        }
+    }
+}
+
+impl<T> Drawable<T> for TextInput<T>
+where
+    T: Copy + Clone,
+{
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if !self.active {
+            return;
+        }
+
+        // text with the character under the cursor highlighted
+        for i in 0..=self.len {
+            let c = if i == self.len { b'_' } else { self.buffer[i] };
+            if i == self.cursor {
+                ctxt.set_color(Color::new(0xFF, 0x00, 0x00, 0xFF));
+            } else if i == self.len {
+                continue;
+            }
+            ctxt.putsu8(&[c, 0], self.x + i as isize * ctxt.char_width(), self.y);
+        }
+
+        ctxt.puts(
+            self.layouts[self.layout].name,
+            self.x,
+            self.y + ctxt.char_height() + 2,
+        );
+        self.keyboard.draw(ctxt);
+    }
+
+    fn update(&mut self, data: T) {
+        self.keyboard.update(data);
+    }
+}
+
+impl<T> Widget<T> for TextInput<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        if self.active {
+            self.cancel();
+        } else {
+            self.open(&[]);
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
+}
@@ -1,11 +1,25 @@
+use super::clock::{write_time, Clock, TimeFormat, VideoStandard};
 use super::color::Color;
-use super::menu::*;
-use super::render::{to_decimal, Drawable, RenderContext, Widget};
-use core::ffi::c_void;
+use super::render::{Drawable, RenderContext, Widget};
+use super::text::TextWriter;
 use core::marker::PhantomData;
 
+/// where the elapsed time comes from
+#[derive(Copy, Clone, PartialEq)]
+pub enum TimeSource {
+    /// real time from the cpu count register
+    Count,
+    /// counted game frames times the fields per frame
+    Frames,
+}
+
 /**
- * A tmter that counts time based on framerate
+ * A timer that measures real time using the count register
+ * or counted frames as a fallback (e.g. for emulators
+ * that do not emulate the count register correctly)
+ * Nothing is measured until start is called.
+ * update keeps the count register time current,
+ * with TimeSource::Frames the caller calls inc once per game frame.
  */
 pub struct Timer<T>
 where
@@ -15,7 +29,13 @@ where
     y: isize,
     pub frames: u32,
     pub active: bool,
-    pub framerate: u32,
+    /// vi fields per game frame for TimeSource::Frames, e.g. 2 for 30fps games
+    pub fields_per_frame: u32,
+    pub source: TimeSource,
+    pub format: TimeFormat,
+    pub standard: VideoStandard,
+    running: bool,
+    clock: Clock,
     phantom: PhantomData<T>,
     buffer: [u8; 32],
 }
@@ -24,36 +44,72 @@ impl<T> Timer<T>
 where
     T: Copy + Clone,
 {
-    pub fn new(x: isize, y: isize) -> Self {
+    pub fn new(x: isize, y: isize, fields_per_frame: u32) -> Self {
+        let standard = VideoStandard::detect();
         Self {
             x,
             y,
             frames: 0,
             active: false,
-            framerate: 20,
+            fields_per_frame: fields_per_frame.max(1),
+            source: TimeSource::Count,
+            format: TimeFormat::Millis,
+            standard,
+            running: false,
+            clock: Clock::new(),
             buffer: [0; 32],
             phantom: PhantomData,
         }
     }
 
+    /// counts a game frame while running
     pub fn inc(&mut self) {
-        self.frames += 1;
+        if self.running {
+            self.frames += 1;
+        }
     }
 
     pub fn set(&mut self, frames: u32) {
         self.frames = frames;
     }
 
-    fn puts_time(time: u32, buffer: &mut [u8]) {
-        let mut start = 0;
+    pub fn start(&mut self) {
+        if !self.running {
+            self.clock.resume();
+            self.running = true;
+        }
+    }
 
-        if time < 10 {
-            buffer[start] = b'0';
-            start = 1;
+    pub fn stop(&mut self) {
+        if self.running {
+            self.clock.update();
+            self.running = false;
         }
+    }
+
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.clock.reset();
+    }
 
-        // seconds
-        to_decimal(time as isize, &mut buffer[start..]);
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    /// elapsed time in milliseconds
+    pub fn elapsed(&self) -> u64 {
+        match self.source {
+            TimeSource::Count => self.clock.millis(),
+            TimeSource::Frames => {
+                let fields = self.frames as u64 * self.fields_per_frame.max(1) as u64;
+                fields * 1_000_000 / self.standard.field_rate_milli()
+            }
+        }
+    }
+
+    /// writes the elapsed time in the current format
+    pub fn write(&self, w: &mut TextWriter) {
+        write_time(self.elapsed(), self.format, w);
     }
 }
 
@@ -61,21 +117,23 @@ impl<T> Drawable<T> for Timer<T>
 where
     T: Copy + Clone,
 {
-    fn update(&mut self, _data: T) {}
+    /// call once a frame, does not count the frame
+    fn update(&mut self, _data: T) {
+        if self.running {
+            self.clock.update();
+        }
+    }
 
     fn draw(&mut self, ctxt: &mut dyn RenderContext) {
         if self.active {
-            // minutes
-            Self::puts_time(self.frames / self.framerate / 60, &mut self.buffer[..]);
-            self.buffer[2] = b':';
-
-            // seconds
-            Self::puts_time(self.frames / self.framerate % 60, &mut self.buffer[3..]);
-            self.buffer[5] = b':';
-
-            // frames
-            Self::puts_time(self.frames % 20, &mut self.buffer[6..]);
+            let millis = self.elapsed();
+            let mut w = TextWriter::new(&mut self.buffer);
+            write_time(millis, self.format, &mut w);
+            w.finish();
 
+            if !self.running {
+                ctxt.set_color(Color::new(0x80, 0x80, 0x80, 0xFF));
+            }
             ctxt.putsu8(&self.buffer, self.x, self.y);
         }
     }
@@ -0,0 +1,196 @@
+use crate::menu::{Entry, EntryTypes};
+use crate::save::SaveBuffer;
+use crate::text::TextWriter;
+use crate::usb::{BiError, Usb};
+use crate::watch::WatchType;
+
+/// the most triggers a single watch can have
+pub const MAX_TRIGGERS: usize = 2;
+
+const EVENT_MAGIC: [u8; 4] = *b"TRIG";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Condition {
+    /// the value is different from the last frame
+    Changes,
+    /// the raw value equals this value
+    Equals(u64),
+    /// the value moved from one side of the threshold to the other
+    Crosses(f32),
+    /// the bit with this index is set
+    BitSet(u8),
+}
+
+impl Condition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Changes => "changed",
+            Self::Equals(_) => "equals",
+            Self::Crosses(_) => "crossed",
+            Self::BitSet(_) => "bit set",
+        }
+    }
+
+    /// id used in usb events
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Changes => 0,
+            Self::Equals(_) => 1,
+            Self::Crosses(_) => 2,
+            Self::BitSet(_) => 3,
+        }
+    }
+
+    /**
+     * Changes and crosses only look at the step between two frames,
+     * the other conditions are true for as long as the value matches
+     */
+    pub(crate) fn is_edge(&self) -> bool {
+        matches!(self, Self::Changes | Self::Crosses(_))
+    }
+
+    pub(crate) fn is_met(
+        &self,
+        previous: Option<u64>,
+        current: u64,
+        watch_type: &WatchType,
+    ) -> bool {
+        match *self {
+            Self::Changes => previous.is_some_and(|previous| previous != current),
+            Self::Equals(value) => current == value,
+            Self::Crosses(threshold) => previous.is_some_and(|previous| {
+                (watch_type.to_f32(previous) < threshold)
+                    != (watch_type.to_f32(current) < threshold)
+            }),
+            Self::BitSet(bit) => bit < 64 && current & (1 << bit) != 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TriggerAction {
+    /// stops the game in frame advance
+    Pause,
+    /// writes a message to the on-screen log
+    Log,
+    /// sends an event to the host
+    Usb,
+    Split,
+    StopTimer,
+}
+
+/**
+ * A condition on a watch value
+ * and what happens when it becomes true
+ */
+#[derive(Copy, Clone)]
+pub struct Trigger {
+    pub condition: Condition,
+    pub action: TriggerAction,
+    pub enabled: bool,
+    was_met: bool,
+}
+
+impl Trigger {
+    pub fn new(condition: Condition, action: TriggerAction) -> Self {
+        Self {
+            condition,
+            action,
+            enabled: true,
+            was_met: false,
+        }
+    }
+
+    /**
+     * Call once a frame with the last two values
+     * returns true when the trigger fires.
+     * Level conditions only fire once until they become false again.
+     */
+    pub fn update(
+        &mut self,
+        previous: Option<u64>,
+        current: Option<u64>,
+        watch_type: &WatchType,
+    ) -> bool {
+        let met = match current {
+            Some(current) => self.condition.is_met(previous, current, watch_type),
+            None => false,
+        };
+
+        let fire = met && (self.condition.is_edge() || !self.was_met);
+        self.was_met = met;
+        self.enabled && fire
+    }
+
+    /// writes the condition for log messages
+    pub fn describe(&self, w: &mut TextWriter) {
+        w.puts(self.condition.name());
+        match self.condition {
+            Condition::Equals(value) => {
+                w.push(b' ');
+                w.dec(value);
+            }
+            Condition::Crosses(threshold) => {
+                w.push(b' ');
+                w.float(threshold);
+            }
+            Condition::BitSet(bit) => {
+                w.push(b' ');
+                w.dec(bit as u64);
+            }
+            Condition::Changes => {}
+        }
+    }
+}
+
+/**
+ * What the pause and timer actions call
+ * the log and usb actions are handled by the watch itself
+ */
+#[derive(Copy, Clone)]
+pub struct TriggerActions<T>
+where
+    T: Copy + Clone,
+{
+    pub pause: EntryTypes<T>,
+    pub split: EntryTypes<T>,
+    pub stop_timer: EntryTypes<T>,
+}
+
+impl<T> TriggerActions<T>
+where
+    T: Copy + Clone,
+{
+    pub fn new(pause: EntryTypes<T>, split: EntryTypes<T>, stop_timer: EntryTypes<T>) -> Self {
+        Self {
+            pause,
+            split,
+            stop_timer,
+        }
+    }
+}
+
+impl<T> Default for TriggerActions<T>
+where
+    T: Copy + Clone,
+{
+    fn default() -> Self {
+        Self::new(Entry::empty(), Entry::empty(), Entry::empty())
+    }
+}
+
+/**
+ * Sends a fired trigger to the host
+ * 24 bytes: "TRIG", watch index, condition id, 2 bytes padding,
+ * address and the raw value (big endian)
+ */
+pub fn send_event(index: usize, trigger: &Trigger, addr: usize, raw: u64) -> Result<(), BiError> {
+    // usb dma needs an 8 byte aligned buffer
+    let mut event = SaveBuffer::<24>::new();
+    event.data[0..4].copy_from_slice(&EVENT_MAGIC);
+    event.data[4] = index as u8;
+    event.data[5] = trigger.condition.id();
+    event.data[8..12].copy_from_slice(&(addr as u32).to_be_bytes());
+    event.data[12..20].copy_from_slice(&raw.to_be_bytes());
+    Usb::write(&mut event.data)
+}
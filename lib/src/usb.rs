@@ -34,6 +34,9 @@ const PI_STATUS_IO_BUSY: usize = 1 << 1;
 const PI_STATUS_ERROR: usize = 1 << 2;
 const KSEG1: usize = 0xA0000000;
 
+/// sram is mapped into pi domain 2
+const SRAM_ADDR: usize = 0x08000000;
+
 const SAVE_OFF: usize = 0;
 const SAVE_EEP4K: usize = 1;
 const SAVE_EEP16K: usize = 2;
@@ -139,12 +142,12 @@ macro_rules! cache_op {
 }
 
 #[inline]
-unsafe fn data_cache_hit_writeback(addr: *mut c_void, len: usize) {
+pub(crate) unsafe fn data_cache_hit_writeback(addr: *mut c_void, len: usize) {
     cache_op!(0x19, addr, len);
 }
 
 #[inline]
-unsafe fn data_cache_hit_writeback_invalidate(addr: *mut c_void, len: usize) {
+pub(crate) unsafe fn data_cache_hit_writeback_invalidate(addr: *mut c_void, len: usize) {
     cache_op!(0x15, addr, len);
 }
 
@@ -310,6 +313,56 @@ unsafe fn evd_set_save_type(stype: usize) {
     evd_reg_write(REG_RAM_CFG as *mut u32, stype as u32);
 }
 
+/**
+ * Sets the domain 2 timing sram needs
+ * returns the previous timing so the game's setup can be restored
+ */
+unsafe fn sram_timing(timing: [u32; 4]) -> [u32; 4] {
+    let regs = [
+        PI_BSD_DOM2_LAT_REG,
+        PI_BSD_DOM2_PWD_REG,
+        PI_BSD_DOM2_PGS_REG,
+        PI_BSD_DOM2_RLS_REG,
+    ];
+    let mut previous = [0; 4];
+    for ((reg, value), previous) in regs.iter().zip(timing.iter()).zip(previous.iter_mut()) {
+        *previous = io_read(*reg as *mut u32);
+        io_write(*reg as *mut u32, *value);
+    }
+    previous
+}
+
+const SRAM_TIMING: [u32; 4] = [0x05, 0x0C, 0x0D, 0x02];
+
+/**
+ * Reads cartridge sram using pi dma
+ * data has to be 8 byte aligned and have an even length
+ */
+pub fn read_sram(offset: usize, data: &mut [u8]) {
+    unsafe {
+        let previous = sram_timing(SRAM_TIMING);
+        pi_read(
+            data.as_mut_ptr() as *mut c_void,
+            (SRAM_ADDR + offset) as *mut c_void,
+            data.len(),
+        );
+        sram_timing(previous);
+    }
+}
+
+/// same requirements as read_sram
+pub fn write_sram(offset: usize, data: &mut [u8]) {
+    unsafe {
+        let previous = sram_timing(SRAM_TIMING);
+        pi_write(
+            data.as_mut_ptr() as *mut c_void,
+            (SRAM_ADDR + offset) as *mut c_void,
+            data.len(),
+        );
+        sram_timing(previous);
+    }
+}
+
 pub fn init_usb_interface() {
     unsafe {
         evd_init();
@@ -1,11 +1,26 @@
-use crate::render::{Drawable, RenderContext};
-use core::{ffi::c_void, marker::PhantomData};
+use crate::graph::{GraphStyle, History, GRAPH_HEIGHT};
+use crate::log::{MessageLog, MESSAGE_LEN};
+use crate::memmap::{MemoryAccess, MemoryError, MemoryMap};
+use crate::pointer::{ChainError, PointerChain, MAX_EXPR_LEN};
+use crate::render::{Drawable, RenderContext, Widget};
+use crate::telemetry::Telemetry;
+use crate::text::TextWriter;
+use crate::textinput::{InputResult, TextInput, POINTER_LAYOUTS};
+use crate::trigger::{self, Trigger, TriggerAction, TriggerActions, MAX_TRIGGERS};
+use core::marker::PhantomData;
+
+pub const MAX_WATCHES: usize = 8;
+pub const MAX_LABEL_LEN: usize = 12;
+
+/// longest line a single watch can draw
+const LINE_LEN: usize = MAX_EXPR_LEN + 24;
 
 /**
  * This is a memory watch type
  * Note that some watch must be aligned to
  * function correctly!
  */
+#[derive(Copy, Clone)]
 pub enum WatchType {
     Float32(*const f32),
     Int8(*const i8),
@@ -18,9 +33,151 @@ pub enum WatchType {
     UInt64(*const u64),
 }
 
+impl WatchType {
+    pub fn addr(&self) -> usize {
+        match *self {
+            Self::Float32(ptr) => ptr as usize,
+            Self::Int8(ptr) => ptr as usize,
+            Self::Int16(ptr) => ptr as usize,
+            Self::Int32(ptr) => ptr as usize,
+            Self::Int64(ptr) => ptr as usize,
+            Self::UInt8(ptr) => ptr as usize,
+            Self::UInt16(ptr) => ptr as usize,
+            Self::UInt32(ptr) => ptr as usize,
+            Self::UInt64(ptr) => ptr as usize,
+        }
+    }
+
+    /// id of the type in telemetry records
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Float32(_) => 0,
+            Self::Int8(_) => 1,
+            Self::Int16(_) => 2,
+            Self::Int32(_) => 3,
+            Self::Int64(_) => 4,
+            Self::UInt8(_) => 5,
+            Self::UInt16(_) => 6,
+            Self::UInt32(_) => 7,
+            Self::UInt64(_) => 8,
+        }
+    }
+
+    /// the same type pointing at a different address
+    pub fn with_addr(&self, addr: usize) -> Self {
+        match self {
+            Self::Float32(_) => Self::Float32(addr as *const f32),
+            Self::Int8(_) => Self::Int8(addr as *const i8),
+            Self::Int16(_) => Self::Int16(addr as *const i16),
+            Self::Int32(_) => Self::Int32(addr as *const i32),
+            Self::Int64(_) => Self::Int64(addr as *const i64),
+            Self::UInt8(_) => Self::UInt8(addr as *const u8),
+            Self::UInt16(_) => Self::UInt16(addr as *const u16),
+            Self::UInt32(_) => Self::UInt32(addr as *const u32),
+            Self::UInt64(_) => Self::UInt64(addr as *const u64),
+        }
+    }
+
+    /// size of the value in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Int8(_) | Self::UInt8(_) => 1,
+            Self::Int16(_) | Self::UInt16(_) => 2,
+            Self::Float32(_) | Self::Int32(_) | Self::UInt32(_) => 4,
+            Self::Int64(_) | Self::UInt64(_) => 8,
+        }
+    }
+
+    pub fn signed(&self) -> bool {
+        matches!(
+            self,
+            Self::Int8(_) | Self::Int16(_) | Self::Int32(_) | Self::Int64(_)
+        )
+    }
+
+    /// the format a new watch of this type starts with
+    pub fn default_format(&self) -> WatchFormat {
+        match self {
+            Self::Float32(_) => WatchFormat::Float,
+            _ if self.signed() => WatchFormat::Dec,
+            _ => WatchFormat::Hex,
+        }
+    }
+
+    /**
+     * Reads the raw bits of the value
+     * every access is volatile and has to be aligned
+     * to the size of the type.
+     * 64 bit values are read as two words.
+     */
+    pub fn read<M: MemoryAccess>(&self, memory: &M) -> Result<u64, MemoryError> {
+        let addr = self.addr();
+        if addr % self.size() != 0 {
+            return Err(MemoryError::Unaligned);
+        }
+
+        Ok(match self.size() {
+            1 => memory.read_u8(addr)? as u64,
+            2 => memory.read_u16(addr)? as u64,
+            4 => memory.read_u32(addr)? as u64,
+            _ => ((memory.read_u32(addr)? as u64) << 32) | memory.read_u32(addr + 4)? as u64,
+        })
+    }
+
+    /// sign extends raw bits read from this type
+    pub fn to_i64(&self, raw: u64) -> i64 {
+        let shift = 64 - self.size() * 8;
+        ((raw << shift) as i64) >> shift
+    }
+
+    /// the numeric value of raw bits read from this type
+    pub fn to_f32(&self, raw: u64) -> f32 {
+        match self {
+            Self::Float32(_) => f32::from_bits(raw as u32),
+            _ if self.signed() => self.to_i64(raw) as f32,
+            _ => raw as f32,
+        }
+    }
+
+    /**
+     * Writes raw bits read from this type in the given format
+     */
+    pub fn format(&self, raw: u64, format: WatchFormat, w: &mut TextWriter) {
+        match format {
+            WatchFormat::Hex => w.hex(raw, self.size() * 2),
+            WatchFormat::Dec if self.signed() => w.signed(self.to_i64(raw)),
+            WatchFormat::Dec => w.dec(raw),
+            WatchFormat::Float => w.float(self.to_f32(raw)),
+        }
+    }
+}
+
+/**
+ * How a value is displayed
+ * Float on an integer shows the integer as a float
+ * and hex on a float shows the raw bits
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub enum WatchFormat {
+    Hex,
+    Dec,
+    Float,
+}
+
+impl WatchFormat {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Hex => Self::Dec,
+            Self::Dec => Self::Float,
+            Self::Float => Self::Hex,
+        }
+    }
+}
+
 /**
  * An address to watch
  */
+#[derive(Copy, Clone)]
 pub struct WatchAddr<T>
 where
     T: Copy,
@@ -28,7 +185,18 @@ where
     pub x: isize,
     pub y: isize,
     pub watch_type: WatchType,
+    pub format: WatchFormat,
     pub active: bool,
+    /// moves the watch to the end of the chain every frame
+    pub chain: Option<PointerChain>,
+    /// the last values for drawing a graph
+    pub history: Option<History>,
+    pub triggers: [Option<Trigger>; MAX_TRIGGERS],
+    label: [u8; MAX_LABEL_LEN + 1],
+    /// raw bits of the last read
+    value: Result<u64, ChainError>,
+    /// raw bits of the read before the last one
+    previous: Option<u64>,
     pahntom: PhantomData<T>,
 }
 
@@ -41,10 +209,184 @@ where
             x,
             y,
             watch_type: addr,
+            format: addr.default_format(),
             active: true,
-            pahntom: PhantomData::default(),
+            chain: None,
+            history: None,
+            triggers: [None; MAX_TRIGGERS],
+            label: [0; MAX_LABEL_LEN + 1],
+            value: Err(ChainError::Memory(MemoryError::Unmapped)),
+            previous: None,
+            pahntom: PhantomData,
         }
     }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.set_label(label.as_bytes());
+        self
+    }
+
+    pub fn with_format(mut self, format: WatchFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_chain(mut self, chain: PointerChain) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    pub fn with_history(mut self, capacity: usize, style: GraphStyle) -> Self {
+        self.history = Some(History::new(capacity, style));
+        self
+    }
+
+    /// starts or stops keeping a history
+    pub fn toggle_history(&mut self, capacity: usize, style: GraphStyle) {
+        self.history = match self.history {
+            Some(_) => None,
+            None => Some(History::new(capacity, style)),
+        };
+    }
+
+    pub fn with_trigger(mut self, trigger: Trigger) -> Self {
+        self.add_trigger(trigger);
+        self
+    }
+
+    /// adds a trigger to the first free slot and returns its index
+    pub fn add_trigger(&mut self, trigger: Trigger) -> Option<usize> {
+        let index = self.triggers.iter().position(|t| t.is_none())?;
+        self.triggers[index] = Some(trigger);
+        Some(index)
+    }
+
+    pub fn remove_trigger(&mut self, index: usize) {
+        if index < MAX_TRIGGERS {
+            self.triggers[index] = None;
+        }
+    }
+
+    /// checks every trigger against the last read, call once after refresh
+    pub fn update_triggers(&mut self) -> [bool; MAX_TRIGGERS] {
+        let mut fired = [false; MAX_TRIGGERS];
+        let current = self.value.ok();
+        for (fired, trigger) in fired.iter_mut().zip(self.triggers.iter_mut()) {
+            if let Some(trigger) = trigger {
+                *fired = trigger.update(self.previous, current, &self.watch_type);
+            }
+        }
+        fired
+    }
+
+    /// height of the line this watch takes up
+    pub fn height(&self, ctxt: &dyn RenderContext) -> isize {
+        if self.history.is_some() {
+            isize::max(GRAPH_HEIGHT, ctxt.char_height())
+        } else {
+            ctxt.char_height()
+        }
+    }
+
+    /// sets the label from a null terminated or plain byte string
+    pub fn set_label(&mut self, label: &[u8]) {
+        self.label.fill(0);
+        for (dst, src) in self
+            .label
+            .iter_mut()
+            .zip(label.iter().take_while(|c| **c != 0))
+            .take(MAX_LABEL_LEN)
+        {
+            *dst = *src;
+        }
+    }
+
+    pub fn label(&self) -> &[u8] {
+        &self.label
+    }
+
+    /**
+     * Reads the current value, call once a frame
+     * pointer chains are followed again every time
+     */
+    pub fn refresh<M: MemoryAccess>(&mut self, memory: &M) {
+        self.previous = self.value.ok();
+        if let Some(chain) = self.chain {
+            match chain.eval(memory) {
+                Ok(addr) => self.watch_type = self.watch_type.with_addr(addr),
+                Err(err) => {
+                    self.value = Err(err);
+                    self.sample();
+                    return;
+                }
+            }
+        }
+        self.value = self.watch_type.read(memory).map_err(ChainError::from);
+        self.sample();
+    }
+
+    /// failed reads are kept as gaps in the history
+    fn sample(&mut self) {
+        let value = match self.value {
+            Ok(raw) => self.watch_type.to_f32(raw),
+            Err(_) => f32::NAN,
+        };
+
+        if let Some(history) = &mut self.history {
+            history.push(value);
+        }
+    }
+
+    /// raw bits of the last read
+    pub fn value(&self) -> Result<u64, ChainError> {
+        self.value
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+    }
+
+    /**
+     * Writes "label: value" into buffer
+     * if there is no label the expression or address is used instead
+     */
+    pub fn format(&self, buffer: &mut [u8]) -> usize {
+        let mut w = TextWriter::new(buffer);
+        self.write_name(&mut w);
+        w.puts(": ");
+        self.write_value(&mut w);
+        w.finish()
+    }
+
+    /// the label, expression or address
+    pub fn write_name(&self, w: &mut TextWriter) {
+        match self.chain {
+            _ if self.label[0] != 0 => w.putsu8(&self.label),
+            Some(chain) => chain.write(w),
+            None => w.hex(self.watch_type.addr() as u64, 8),
+        }
+    }
+
+    /// the last value in the watch's format or why it could not be read
+    pub fn write_value(&self, w: &mut TextWriter) {
+        match self.value {
+            Ok(raw) => self.watch_type.format(raw, self.format, w),
+            Err(ChainError::Null) => w.puts("null"),
+            Err(ChainError::Memory(MemoryError::Unmapped)) => w.puts("unmapped"),
+            Err(ChainError::Memory(MemoryError::Denied)) => w.puts("denied"),
+            Err(ChainError::Memory(MemoryError::Unaligned)) => w.puts("unaligned"),
+        }
+    }
+
+    /// history samples are shown as floats or signed decimals
+    fn format_sample(&self, value: f32, buffer: &mut [u8]) -> usize {
+        let mut w = TextWriter::new(buffer);
+        match self.format {
+            WatchFormat::Float => w.float(value),
+            _ => w.signed(value as i64),
+        }
+        w.finish()
+    }
 }
 
 impl<T> Drawable<T> for WatchAddr<T>
@@ -52,18 +394,40 @@ where
     T: Copy,
 {
     fn draw(&mut self, ctxt: &mut dyn RenderContext) {
-        // decide which type to read
-        match self.watch_type {
-            WatchType::Int8(ptr) => {}
-            _ => todo!("Not implemented"),
+        if !self.active {
+            return;
+        }
+
+        let mut line = [0u8; LINE_LEN];
+        let len = self.format(&mut line) as isize;
+        ctxt.putsu8(&line, self.x, self.y);
+
+        if let Some(history) = &self.history {
+            let graph_x = self.x + (len + 1) * ctxt.char_width();
+            let width = history.draw(ctxt, graph_x, self.y);
+
+            // min and max next to the graph
+            let label_x = graph_x + width + ctxt.char_width();
+            if let Some((_, max)) = history.max() {
+                self.format_sample(max, &mut line);
+                ctxt.putsu8(&line, label_x, self.y);
+            }
+            if let Some((_, min)) = history.min() {
+                self.format_sample(min, &mut line);
+                ctxt.putsu8(&line, label_x, self.y + GRAPH_HEIGHT - ctxt.char_height());
+            }
         }
     }
 
-    fn update(&mut self, data: T) {}
+    fn update(&mut self, _data: T) {}
 }
 
 /**
  * A drawable watch object
+ * Active watches are drawn as a list starting at x and y,
+ * disabled watches do not take up a line.
+ * New pointer chain watches are typed into input,
+ * the caller forwards the buttons to it while it is active.
  */
 pub struct Watch<T>
 where
@@ -71,5 +435,204 @@ where
 {
     pub x: isize,
     pub y: isize,
-    pub watch_list: [WatchAddr<T>; 8],
+    pub active: bool,
+    pub memory: MemoryMap,
+    pub watch_list: [Option<WatchAddr<T>>; MAX_WATCHES],
+    pub input: TextInput<T>,
+    /// receives the messages of log triggers
+    pub log: MessageLog<T>,
+    pub actions: TriggerActions<T>,
+    /// streams the values to the host while active
+    pub telemetry: Telemetry,
+    /// type of the watch that is created once the input is confirmed
+    input_type: WatchType,
+}
+
+impl<T> Watch<T>
+where
+    T: Copy,
+{
+    pub fn new(x: isize, y: isize) -> Self {
+        Self {
+            x,
+            y,
+            active: true,
+            memory: MemoryMap::detect(),
+            watch_list: [None; MAX_WATCHES],
+            input: TextInput::new(x, y, MAX_EXPR_LEN, &POINTER_LAYOUTS)
+                .with_validator(PointerChain::is_valid)
+                .with_char_filter(PointerChain::is_expr_char),
+            log: MessageLog::new(x, y + (MAX_WATCHES as isize + 1) * (GRAPH_HEIGHT + 2)),
+            actions: TriggerActions::default(),
+            telemetry: Telemetry::new(),
+            input_type: WatchType::UInt32(core::ptr::null()),
+        }
+    }
+
+    pub fn with_actions(mut self, actions: TriggerActions<T>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /**
+     * Asks for a pointer chain expression
+     * and adds a watch of the given type for it
+     */
+    pub fn expr_input(&mut self, watch_type: WatchType) {
+        self.input_type = watch_type;
+        self.input.open(&[]);
+    }
+
+    /// adds a watch to the first free slot and returns its index
+    pub fn add(&mut self, watch: WatchAddr<T>) -> Option<usize> {
+        let index = self.watch_list.iter().position(|w| w.is_none())?;
+        self.watch_list[index] = Some(watch);
+        Some(index)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < MAX_WATCHES {
+            self.watch_list[index] = None;
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&WatchAddr<T>> {
+        self.watch_list.get(index)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut WatchAddr<T>> {
+        self.watch_list.get_mut(index)?.as_mut()
+    }
+
+    /// enables or disables a single watch
+    pub fn toggle_watch(&mut self, index: usize) {
+        if let Some(watch) = self.get_mut(index) {
+            watch.toggle();
+        }
+    }
+
+    pub fn cycle_format(&mut self, index: usize) {
+        if let Some(watch) = self.get_mut(index) {
+            watch.format = watch.format.next();
+        }
+    }
+}
+
+/**
+ * Runs the action of a trigger that fired
+ */
+fn fire<T: Copy + Clone>(
+    actions: &mut TriggerActions<T>,
+    log: &mut MessageLog<T>,
+    index: usize,
+    watch: &WatchAddr<T>,
+    trigger: &Trigger,
+    data: T,
+) {
+    match trigger.action {
+        TriggerAction::Pause => actions.pause.activate(data),
+        TriggerAction::Split => actions.split.activate(data),
+        TriggerAction::StopTimer => actions.stop_timer.activate(data),
+        TriggerAction::Log => {
+            let mut message = [0u8; MESSAGE_LEN];
+            let mut w = TextWriter::new(&mut message);
+            watch.write_name(&mut w);
+            w.push(b' ');
+            trigger.describe(&mut w);
+            w.puts(": ");
+            watch.write_value(&mut w);
+            w.finish();
+            log.push(&message);
+        }
+        TriggerAction::Usb => {
+            if let Ok(raw) = watch.value() {
+                // there is no one to report to if the host is not listening
+                let _ = trigger::send_event(index, trigger, watch.watch_type.addr(), raw);
+            }
+        }
+    }
+}
+
+impl<T> Drawable<T> for Watch<T>
+where
+    T: Copy + Clone,
+{
+    fn draw(&mut self, ctxt: &mut dyn RenderContext) {
+        if self.input.active() {
+            self.input.draw(ctxt);
+            return;
+        }
+
+        if !self.active {
+            return;
+        }
+
+        let mut y = self.y;
+        for watch in self.watch_list.iter_mut().flatten() {
+            if !watch.active {
+                continue;
+            }
+
+            watch.x = self.x;
+            watch.y = y;
+            watch.draw(ctxt);
+            y += watch.height(ctxt) + 2;
+        }
+        self.log.draw(ctxt);
+    }
+
+    fn update(&mut self, data: T) {
+        self.input.update(data);
+        if self.input.take_result() == InputResult::Confirmed {
+            // the validator already made sure the expression parses
+            if let Ok(chain) = PointerChain::parse(self.input.text()) {
+                let watch = WatchAddr::new(self.x, self.y, self.input_type).with_chain(chain);
+                self.add(watch);
+            }
+        }
+
+        if !self.active {
+            return;
+        }
+
+        for (index, slot) in self.watch_list.iter_mut().enumerate() {
+            let watch = match slot {
+                Some(watch) => watch,
+                None => continue,
+            };
+
+            if watch.active {
+                watch.refresh(&self.memory);
+                let fired = watch.update_triggers();
+                for (trigger, fired) in watch.triggers.iter().zip(fired) {
+                    if let (Some(trigger), true) = (trigger, fired) {
+                        fire(
+                            &mut self.actions,
+                            &mut self.log,
+                            index,
+                            watch,
+                            trigger,
+                            data,
+                        );
+                    }
+                }
+            }
+            watch.update(data);
+        }
+        self.telemetry.send(&self.memory, &self.watch_list);
+        self.log.update(data);
+    }
+}
+
+impl<T> Widget<T> for Watch<T>
+where
+    T: Copy + Clone,
+{
+    fn toggle(&mut self, _data: T) {
+        self.active = !self.active;
+    }
+
+    fn active(&self) -> bool {
+        self.active
+    }
 }